@@ -49,20 +49,11 @@ impl<'a> MAPFEnvironmentCabooseCompat<'a> {
         for i0 in 0..s.definition.shape.0 {
             for i1 in 0..s.definition.shape.1 {
                 if let Some(from_id) = nodes_lookup.get(&(i0, i1)) {
-                    if let Some(to_id) = nodes_lookup.get(&(i0 - 1, i1)) {
-                        g.add_edge(*from_id, *to_id, 1f64);
-                    }
-
-                    if let Some(to_id) = nodes_lookup.get(&(i0, i1 - 1)) {
-                        g.add_edge(*from_id, *to_id, 1f64);
-                    }
-
-                    if let Some(to_id) = nodes_lookup.get(&(i0 + 1, i1)) {
-                        g.add_edge(*from_id, *to_id, 1f64);
-                    }
-
-                    if let Some(to_id) = nodes_lookup.get(&(i0, i1 + 1)) {
-                        g.add_edge(*from_id, *to_id, 1f64);
+                    // waiting is covered by can_wait_at
+                    for to in s.definition.neighbours((i0, i1)).into_iter().filter(|&to| to != (i0, i1)) {
+                        if let Some(to_id) = nodes_lookup.get(&to) {
+                            g.add_edge(*from_id, *to_id, s.definition.cost(to) as f64);
+                        }
                     }
                 }
             }
@@ -85,8 +76,8 @@ impl<'a> TransitionSystem<SimpleState, GraphEdgeId, LimitedValue<f64>, LimitedVa
         SimpleState(self.graph.get_edge(*action).to)
     }
 
-    fn transition_cost(&self, state: &SimpleState, action: &GraphEdgeId) -> LimitedValue<f64> {
-        1f64.into()
+    fn transition_cost(&self, _state: &SimpleState, action: &GraphEdgeId) -> LimitedValue<f64> {
+        self.graph.get_edge(*action).data.into()
     }
 
     fn reverse_actions_from(&self, state: &SimpleState) -> Iter<GraphEdgeId> {
@@ -97,8 +88,8 @@ impl<'a> TransitionSystem<SimpleState, GraphEdgeId, LimitedValue<f64>, LimitedVa
         SimpleState(self.graph.get_edge(*action).from)
     }
 
-    fn reverse_transition_cost(&self, state: &SimpleState, action: &GraphEdgeId) -> LimitedValue<f64> {
-        1f64.into()
+    fn reverse_transition_cost(&self, _state: &SimpleState, action: &GraphEdgeId) -> LimitedValue<f64> {
+        self.graph.get_edge(*action).data.into()
     }
 
     fn can_wait_at(&self, state: &SimpleState) -> bool {
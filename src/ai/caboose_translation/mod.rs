@@ -11,7 +11,7 @@ use crate::ai::caboose_translation::limited_float::LimitedValue;
 use crate::ai::caboose_translation::mapf_transition_environment::{MAPFEnvironmentCabooseCompat, SimpleState};
 use crate::mapf::action::MAPFAction;
 use crate::mapf::environment::MAPFEnvironment;
-use crate::mapf::state::MAPFState;
+use crate::mapf::observation::Observation;
 
 pub struct CabooseAI{
     player_id: u8,
@@ -27,9 +27,9 @@ impl CabooseAI {
 
 
 impl AI for CabooseAI {
-    fn next(&mut self, s: &MAPFState, e: &MAPFEnvironment) -> MAPFAction {
-
-        let env_compat = MAPFEnvironmentCabooseCompat::new(s, e, self.player_id);
+    fn next(&mut self, o: &Observation, e: &MAPFEnvironment) -> MAPFAction {
+        let s = o.determinize();
+        let env_compat = MAPFEnvironmentCabooseCompat::new(&s, e, self.player_id);
 
         let tasks = vec![
             Arc::new(Task::new(
@@ -1,10 +1,12 @@
 use crate::ai::AI;
-use crate::deps::state_definition::StateEnvironment;
+use crate::deps::state_definition::FallibleStateEnvironment;
 use crate::mapf::action::MAPFAction::Move;
-use crate::mapf::action::{MAPFAction, MOVES};
+use crate::mapf::action::MAPFAction;
 use crate::mapf::environment::MAPFEnvironment;
+use crate::mapf::observation::Observation;
 use crate::mapf::state::MAPFState;
-use std::collections::VecDeque;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
 use std::sync::Arc;
 
 pub struct GreedyAI {
@@ -20,51 +22,40 @@ impl GreedyAI {
 }
 
 impl AI for GreedyAI {
-    fn next(self: &mut Self, s: &MAPFState, e: &MAPFEnvironment) -> MAPFAction
+    fn next(self: &mut Self, o: &Observation, e: &MAPFEnvironment) -> MAPFAction
     where
         Self: Sized,
     {
+        self.choose(&o.state, e)
+    }
+}
+
+impl GreedyAI {
+    /// Moves the unit that gets closest to a goal, only own units are considered so any state will do
+    pub fn choose(&mut self, s: &MAPFState, e: &MAPFEnvironment) -> MAPFAction {
         if self.grid.is_none() {
             let mut distances: Vec<Vec<i64>> = vec![vec![-1; s.definition.shape.1]; s.definition.shape.0];
-            let mut queue: VecDeque<(usize, usize, i64)> = VecDeque::new();
+            let mut queue: BinaryHeap<Reverse<(i64, usize, usize)>> = BinaryHeap::new();
 
             for (i_a0, i_a1) in s.definition.goals.get_nnz_list() {
                 let a0 = i_a0 as usize;
                 let a1 = i_a1 as usize;
                 distances[a0][a1] = 0;
-                queue.push_back((a0, a1, 0));
+                queue.push(Reverse((0, a0, a1)));
             }
 
-            while !queue.is_empty() {
-                let (i_a0, i_a1, distance) = queue.pop_front().unwrap();
-
-                for (da0, da1) in MOVES {
-                    let na0_ = (da0) + (i_a0 as isize);
-                    let na1_ = (da1) + (i_a1 as isize);
-
-                    if na0_ < 0 || na0_ >= s.definition.shape.0 as isize {
-                        continue;
-                    }
-
-                    if na1_ < 0 || na1_ >= s.definition.shape.1 as isize {
-                        continue;
-                    }
-
-                    let na0 = na0_ as usize;
-                    let na1 = na1_ as usize;
+            while let Some(Reverse((distance, i_a0, i_a1))) = queue.pop() {
+                if distance > distances[i_a0][i_a1] {
+                    continue;
+                }
 
-                    if s.definition
-                        .obstacles
-                        .get(na0, na1)
-                        .unwrap_or(0)
-                        != 0
-                    {
-                        continue;
-                    }
+                // Distances to the goal, so walk the moves backwards paying for the cell entered
+                let distance = distance + s.definition.cost((i_a0, i_a1)) as i64;
 
-                    if distances[na0][na1] == -1 {
-                        distances[na0][na1] = distance + 1;
-                        queue.push_back((na0, na1, distance + 1));
+                for (na0, na1) in s.definition.predecessors((i_a0, i_a1)) {
+                    if distances[na0][na1] == -1 || distances[na0][na1] > distance {
+                        distances[na0][na1] = distance;
+                        queue.push(Reverse((distance, na0, na1)));
                     }
                 }
             }
@@ -72,7 +63,8 @@ impl AI for GreedyAI {
             self.grid = Arc::new(Some(distances));
         }
 
-        let actions = e.get_actions(s);
+        // Sentinel states have no moves, the AI just commits
+        let actions = e.try_get_actions(s).unwrap_or_default();
 
         let mut best_move: Option<MAPFAction> = None;
         let mut best_distance: Option<i64> = None;
@@ -111,7 +103,7 @@ mod tests {
         let env = MAPFEnvironment::new_from_file("./maps/test_hall.test.txt").unwrap();
         let state = env.get_initial_state();
         let mut greedy_ai = GreedyAI::new();
-        let m = greedy_ai.next(&state, &env);
+        let m = greedy_ai.next(&env.observe(&state, state.playing), &env);
         assert_eq!(m, MAPFAction::Move((1, 4), (1, 5)));
     }
 }
\ No newline at end of file
@@ -3,6 +3,7 @@ use crate::ai::AI;
 use crate::deps::state_definition::{StateEnvironment, StateStatus};
 use crate::mapf::action::MAPFAction;
 use crate::mapf::environment::MAPFEnvironment;
+use crate::mapf::observation::Observation;
 use crate::mapf::state::MAPFState;
 use rand::rngs::ThreadRng;
 use rand::{rng, Rng};
@@ -97,8 +98,8 @@ impl MCTSAI {
         for _ in 0..self.simulation_limit {
             match env.get_status(&current_state) {
                 StateStatus::Running => {
-                    let action = random_ai.next(&current_state, env);
-                    current_state = env.next(&current_state, &action);
+                    let action = random_ai.choose(&current_state, env);
+                    env.apply(&mut current_state, &action).unwrap();
                 }
                 StateStatus::Winner(winner) => {
                     return if winner == self.me as u64 { 1.0 } else { 0.0 };
@@ -141,10 +142,22 @@ impl MCTSAI {
 }
 
 impl AI for MCTSAI {
-    fn next(self: &mut Self, s: &MAPFState, e: &MAPFEnvironment) -> MAPFAction {
+    fn name(&self) -> String {
+        format!(
+            "MCTSAI exploration_weight={} simulation_limit={} time_limit_ms={}",
+            self.exploration_weight,
+            self.simulation_limit,
+            self.time_limit.as_millis()
+        )
+    }
+
+    fn next(self: &mut Self, o: &Observation, e: &MAPFEnvironment) -> MAPFAction {
         let start_time = Instant::now();
         let mut nodes = Vec::new();
 
+        // Searches a single guess of the hidden units
+        let s = &o.determinize();
+
         let root_index = 0;
         nodes.push(MCTSNode {
             state: s.clone(),
@@ -193,7 +206,7 @@ mod tests {
         let env = MAPFEnvironment::new_from_file("./maps/test_hall_run.test.txt").unwrap();
         let state = env.get_initial_state();
         let mut mcts_ai = MCTSAI::new(1, 1.414, 50, 100);
-        let m = mcts_ai.next(&state, &env);
+        let m = mcts_ai.next(&env.observe(&state, 1), &env);
         assert!(matches!(m, MAPFAction::Move(_, _) | MAPFAction::Commit));
     }
 
@@ -202,7 +215,7 @@ mod tests {
         let env = MAPFEnvironment::new_from_file("./maps/test_hall_run.test.txt").unwrap();
         let state = env.get_initial_state();
         let mut greedy_ai = MCTSAI::new(1, 1.414, 100, 1000);
-        let m = greedy_ai.next(&state, &env);
+        let m = greedy_ai.next(&env.observe(&state, 1), &env);
         assert_eq!(m, MAPFAction::Move((1, 4), (1, 5)));
     }
 
@@ -218,8 +231,8 @@ mod tests {
             let a;
 
             match state.playing {
-                1 => {a = mcts.next(&state, &env);}
-                2 => {a = random_ai.next(&state, &env);}
+                1 => {a = mcts.next(&env.observe(&state, 1), &env);}
+                2 => {a = random_ai.next(&env.observe(&state, 2), &env);}
                 _ => {panic!("eh?")}
             }
 
@@ -1,6 +1,6 @@
 use crate::mapf::action::MAPFAction;
 use crate::mapf::environment::MAPFEnvironment;
-use crate::mapf::state::MAPFState;
+use crate::mapf::observation::Observation;
 
 pub mod greedy;
 pub mod mcts;
@@ -8,6 +8,17 @@ pub mod random_ai;
 pub mod caboose_translation;
 
 
+/// A player. AIs only ever get to see the observation of the player they play as, see `MAPFEnvironment::observe`.
 pub trait AI {
-    fn next(self: &mut Self, s: &MAPFState, e: &MAPFEnvironment) -> MAPFAction;
+    fn next(self: &mut Self, o: &Observation, e: &MAPFEnvironment) -> MAPFAction;
+
+    /// Restarts the random number generator of the AI from `seed`, so that a game can be played again. AIs
+    /// without randomness ignore it.
+    fn seed(&mut self, _seed: u64) {}
+
+    /// Name of the AI and its parameters, as written to game records
+    fn name(&self) -> String {
+        let name = std::any::type_name::<Self>();
+        name.rsplit("::").next().unwrap_or(name).to_string()
+    }
 }
\ No newline at end of file
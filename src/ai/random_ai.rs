@@ -2,27 +2,31 @@ use crate::ai::AI;
 use crate::deps::state_definition::StateEnvironment;
 use crate::mapf::action::MAPFAction;
 use crate::mapf::environment::MAPFEnvironment;
-use crate::mapf::state::MAPFState;
-use rand::rng;
-use rand::rngs::ThreadRng;
+use crate::mapf::observation::Observation;
+use rand::rngs::StdRng;
 use rand::seq::IndexedRandom;
+use rand::SeedableRng;
 
 pub struct RandomAI {
-    rng: ThreadRng,
+    rng: StdRng,
 }
 
 impl RandomAI {
     pub fn new() -> RandomAI {
-        RandomAI { rng: rng() }
+        RandomAI { rng: StdRng::from_os_rng() }
     }
 }
 
 impl AI for RandomAI {
-    fn next(self: &mut Self, s: &MAPFState, e: &MAPFEnvironment) -> MAPFAction
+    fn seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    fn next(self: &mut Self, o: &Observation, e: &MAPFEnvironment) -> MAPFAction
     where
         Self: Sized,
     {
-        let actions = e.get_actions(s);
+        let actions = e.get_actions(&o.state);
         actions
             .choose(&mut self.rng)
             .cloned()
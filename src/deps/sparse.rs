@@ -1,28 +1,45 @@
 use std::fmt;
 use std::fmt::Formatter;
+use std::hash::{Hash, Hasher};
 
-#[derive(Debug, Eq, PartialEq, Hash, Clone)]
-pub struct SparseMatrix2D {
-    pub data: Vec<Option<Vec<u8>>>,
+/// Row-sparse 2D matrix, the default (zero) value of `T` marks an empty cell
+#[derive(Debug, Clone)]
+pub struct SparseMatrix2D<T = u8> {
+    pub data: Vec<Option<Vec<T>>>,
     pub shape: (usize, usize),
 }
 
-impl SparseMatrix2D {
-    pub(crate) fn bit_reduce_inline(&mut self) {
-        for i in 0..self.data.len() {
-            if let Some(ref mut vec) = self.data[i] {
-                for j in 0..vec.len() {
-                    let x = vec[j];
-                    if x.count_ones() > 1 {
-                        vec[j] = 0;
-                    }
+// Equality and hashing ignore the layout, a missing row equals a row of empty cells
+impl<T: Copy + Default + PartialEq> PartialEq for SparseMatrix2D<T> {
+    fn eq(&self, other: &Self) -> bool {
+        let empty = |row: &Vec<T>| row.iter().all(|&value| value == T::default());
+
+        self.shape == other.shape
+            && self.data.iter().zip(&other.data).all(|rows| match rows {
+                (Some(a), Some(b)) => a == b,
+                (Some(row), None) | (None, Some(row)) => empty(row),
+                (None, None) => true,
+            })
+    }
+}
+
+impl<T: Copy + Default + Eq> Eq for SparseMatrix2D<T> {}
+
+impl<T: Copy + Default + PartialEq + Hash> Hash for SparseMatrix2D<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.shape.hash(state);
+
+        for (a0, row) in self.data.iter().enumerate() {
+            for (a1, &value) in row.iter().flatten().enumerate() {
+                if value != T::default() {
+                    (a0, a1, value).hash(state);
                 }
             }
         }
     }
 }
 
-impl fmt::Display for SparseMatrix2D {
+impl<T: fmt::Display> fmt::Display for SparseMatrix2D<T> {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         for vec in &self.data {
             match vec {
@@ -43,9 +60,9 @@ impl fmt::Display for SparseMatrix2D {
     }
 }
 
-impl SparseMatrix2D {
+impl<T: Copy + Default + PartialEq> SparseMatrix2D<T> {
     #[inline(always)]
-    pub fn get(&self, a0: usize, a1: usize) -> Option<u8> {
+    pub fn get(&self, a0: usize, a1: usize) -> Option<T> {
         self.data
             .get(a0)
             .and_then(|vector| vector.as_ref())
@@ -53,40 +70,16 @@ impl SparseMatrix2D {
     }
 
     #[inline(always)]
-    pub fn get_checked(&self, a0: usize, a1: usize) -> u8 {
+    pub fn get_checked(&self, a0: usize, a1: usize) -> T {
         if a0 >= self.shape.0 || a1 >= self.shape.1 {
             panic!("Index out of bounds: ({}, {}) is outside of shape {:?}", a0, a1, self.shape);
         }
         match &self.data[a0] {
             Some(row) => row[a1],
-            None => 0,
+            None => T::default(),
         }
     }
 
-    pub fn xor_inline(&mut self, other: &SparseMatrix2D) {
-        for (a0_index, other_vec_opt) in other.data.iter().enumerate() {
-            if other_vec_opt.is_none() {
-                continue;
-            }
-            let vec = self.data[a0_index].get_or_insert_with(|| vec![0; self.shape.1]);
-
-            for (a1_index, val) in other_vec_opt.as_ref().unwrap().iter().enumerate() {
-                vec[a1_index] ^= val;
-            }
-        }
-    }
-
-    pub fn xor(&self, other: &SparseMatrix2D) -> SparseMatrix2D {
-        let mut ret: Self = self.clone();
-        ret.xor_inline(other);
-        ret
-    }
-
-    pub fn xor_inline_by_idx(&mut self, a0: usize, a1: usize, val: u8) -> &mut SparseMatrix2D {
-        self.insert(a0, a1, self.get(a0, a1).unwrap_or(0) ^ val);
-        self
-    }
-
     pub fn new(a0_len: usize, a1_len: usize) -> Self {
         SparseMatrix2D {
             data: vec![None; a0_len],
@@ -97,16 +90,14 @@ impl SparseMatrix2D {
     pub fn new_by_shape(shape: (usize, usize)) -> Self {
         Self::new(shape.0, shape.1)
     }
-}
 
-impl SparseMatrix2D {
-    pub fn insert(&mut self, a0_idx: usize, a1_idx: usize, value: u8) {
+    pub fn insert(&mut self, a0_idx: usize, a1_idx: usize, value: T) {
         if a0_idx >= self.shape.0 || a1_idx >= self.shape.1 {
             return;
         }
 
         if self.data[a0_idx].is_none() {
-            self.data[a0_idx] = Some(vec![0; self.shape.1]);
+            self.data[a0_idx] = Some(vec![T::default(); self.shape.1]);
         }
 
         if let Some(row_data) = self.data[a0_idx].as_mut() {
@@ -114,13 +105,23 @@ impl SparseMatrix2D {
         }
     }
 
-    pub fn get_nnz(&self) -> Vec<(usize, usize, u8)> {
+    pub fn clear_value(&mut self, value: T) {
+        for row in self.data.iter_mut().flatten() {
+            for cell in row.iter_mut() {
+                if *cell == value {
+                    *cell = T::default();
+                }
+            }
+        }
+    }
+
+    pub fn get_nnz(&self) -> Vec<(usize, usize, T)> {
         let mut result = Vec::new();
 
         for (a0_index, row_opt) in self.data.iter().enumerate() {
             if let Some(row) = row_opt {
                 for (a1_index, &value) in row.iter().enumerate() {
-                    if value != 0 {
+                    if value != T::default() {
                         result.push((a0_index, a1_index, value));
                     }
                 }
@@ -136,7 +137,7 @@ impl SparseMatrix2D {
         for (_, row_opt) in self.data.iter().enumerate() {
             if let Some(row) = row_opt {
                 for (_, &value) in row.iter().enumerate() {
-                    if value != 0 {
+                    if value != T::default() {
                         count += 1;
                     }
                 }
@@ -146,13 +147,23 @@ impl SparseMatrix2D {
         count
     }
 
+    pub fn count_value(&self, value: T) -> u64 {
+        let mut count: u64 = 0;
+
+        for row in self.data.iter().flatten() {
+            count += row.iter().filter(|&&v| v == value).count() as u64;
+        }
+
+        count
+    }
+
     pub fn get_nnz_list(&self) -> Vec<(u64, u64)> {
         let mut vec: Vec<(u64, u64)> = vec![];
 
         for (a0_idx, row_opt) in self.data.iter().enumerate() {
             if let Some(row) = row_opt {
                 for (a1_idx, &value) in row.iter().enumerate() {
-                    if value != 0 {
+                    if value != T::default() {
                         vec.push((a0_idx as u64, a1_idx as u64));
                     }
                 }
@@ -163,6 +174,32 @@ impl SparseMatrix2D {
     }
 }
 
+impl SparseMatrix2D {
+    pub fn xor_inline(&mut self, other: &SparseMatrix2D) {
+        for (a0_index, other_vec_opt) in other.data.iter().enumerate() {
+            if other_vec_opt.is_none() {
+                continue;
+            }
+            let vec = self.data[a0_index].get_or_insert_with(|| vec![0; self.shape.1]);
+
+            for (a1_index, val) in other_vec_opt.as_ref().unwrap().iter().enumerate() {
+                vec[a1_index] ^= val;
+            }
+        }
+    }
+
+    pub fn xor(&self, other: &SparseMatrix2D) -> SparseMatrix2D {
+        let mut ret: Self = self.clone();
+        ret.xor_inline(other);
+        ret
+    }
+
+    pub fn xor_inline_by_idx(&mut self, a0: usize, a1: usize, val: u8) -> &mut SparseMatrix2D {
+        self.insert(a0, a1, self.get(a0, a1).unwrap_or(0) ^ val);
+        self
+    }
+}
+
 pub struct SparseMatrixFullIter<'a> {
     matrix_data: &'a SparseMatrix2D,
     idx0: usize,
@@ -285,4 +322,17 @@ mod tests {
             println!("{a0}, {a1} = {val}")
         }
     }
+
+    #[test]
+    fn test_eq_ignores_layout(){
+        let mut m = SparseMatrix2D::new_by_shape((3, 3));
+        let empty: SparseMatrix2D = SparseMatrix2D::new_by_shape((3, 3));
+
+        m.insert(1, 1, 4);
+        assert_ne!(m, empty);
+
+        m.insert(1, 1, 0);
+        assert!(m.data[1].is_some());
+        assert_eq!(m, empty);
+    }
 }
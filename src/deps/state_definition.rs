@@ -1,7 +1,7 @@
 use std::sync::Arc;
 
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum StateStatus {
     Running,
     Winner(u64),
@@ -13,4 +13,13 @@ pub(crate) trait StateEnvironment<S, A> {
     fn get_actions(&self, state: &S) -> Arc<Vec<A>>;
     fn next(&self, s: &S, a: &A) -> S;
     fn get_status(&self, s: &S) -> StateStatus;
+}
+
+/// `StateEnvironment` that reports states and actions it can not handle instead of panicking
+pub(crate) trait FallibleStateEnvironment<S, A> {
+    type Error;
+
+    fn try_get_actions(&self, s: &S) -> Result<Arc<Vec<A>>, Self::Error>;
+    fn try_next(&self, s: &S, a: &A) -> Result<S, Self::Error>;
+    fn try_get_status(&self, s: &S) -> Result<StateStatus, Self::Error>;
 }
\ No newline at end of file
@@ -1,11 +1,15 @@
 extern crate derive_more;
 
 use crate::ai::AI;
-use crate::deps::state_definition::{StateEnvironment, StateStatus};
+use crate::deps::state_definition::{FallibleStateEnvironment, StateEnvironment, StateStatus};
 use crate::mapf::environment::MAPFEnvironment;
+use crate::mapf::outcome::Outcome;
+use crate::mapf::record::{GameRecord, RecordEntry};
+use crate::mapf::state::MAPFState;
 use derive_more::Display;
 use std::error::Error;
 use std::fmt::Debug;
+use std::path::PathBuf;
 use std::time::{Duration, Instant};
 
 #[derive(Debug, Display)]
@@ -17,6 +21,25 @@ pub struct NotFinished {}
 impl Error for TimeoutError{}
 impl Error for NotFinished{}
 
+/// Every player of the map needs its own actor
+#[derive(Debug, thiserror::Error)]
+#[error("The map has {players} players, but {actors} actors were given")]
+pub struct ActorCountMismatch {
+    pub players: usize,
+    pub actors: usize,
+}
+
+/// Checks that there is an actor for every player of `mapf`, in turn order
+pub(crate) fn check_actors(mapf: &MAPFEnvironment, actors: &[Box<dyn AI>]) -> Result<(), ActorCountMismatch> {
+    let players = mapf.definition.players.len();
+
+    if actors.len() != players {
+        return Err(ActorCountMismatch { players, actors: actors.len() });
+    }
+
+    Ok(())
+}
+
 macro_rules! args {
     ($ty:ty, $($field:ident : $value:expr),* $(,)?) => {{
         #[allow(unused_mut)]
@@ -29,10 +52,15 @@ macro_rules! args {
 pub(crate) use args;
 use crate::mapf::action::MAPFAction;
 
+#[derive(Clone)]
 pub struct EvaluateAIParams {
     timeout: Duration,
     pub(crate) max_iters: u64,
     pub(crate) verbose: bool,
+    /// Where to write the record of the game, it is written however the game ends
+    pub(crate) record: Option<PathBuf>,
+    /// Seeds the AIs, player by player from consecutive seeds, and is stored in the record
+    pub(crate) seed: Option<u64>,
 }
 
 impl Default for EvaluateAIParams {
@@ -40,38 +68,62 @@ impl Default for EvaluateAIParams {
         EvaluateAIParams{
             timeout: Duration::from_mins(5),
             max_iters: 100_000,
-            verbose: false
+            verbose: false,
+            record: None,
+            seed: None,
         }
     }
 }
 
-pub fn evaluate_ai(mapf: &MAPFEnvironment, mut actors: Vec<Box<dyn AI>>, params: EvaluateAIParams) -> Result<u64, Box<dyn Error>>{
+pub fn evaluate_ai(mapf: &MAPFEnvironment, mut actors: Vec<Box<dyn AI>>, params: EvaluateAIParams) -> Result<Outcome, Box<dyn Error>>{
+    check_actors(mapf, &actors)?;
+
+    if let Some(seed) = params.seed {
+        for (idx, actor) in actors.iter_mut().enumerate() {
+            actor.seed(seed.wrapping_add(idx as u64));
+        }
+    }
+
+    let mut record = GameRecord::new(mapf);
+    record.seed = params.seed;
+    record.players = mapf.definition.players.iter()
+        .zip(actors.iter())
+        .map(|(&player, actor)| (player, actor.name()))
+        .collect();
+
+    let result = play(mapf, &mut actors, &params, &mut record);
+
+    if let Some(path) = &params.record {
+        record.save_to_file(path)?;
+    }
+
+    result
+}
+
+fn play(mapf: &MAPFEnvironment, actors: &mut [Box<dyn AI>], params: &EvaluateAIParams, record: &mut GameRecord) -> Result<Outcome, Box<dyn Error>> {
     let mut state = mapf.get_initial_state();
 
     let start = Instant::now();
 
     for iteration in 0..params.max_iters {
-        let playing = state.playing;
-        let action = actors[(playing - 1) as usize].next(&state, &mapf);
-        state = mapf.next(&state, &action);
-
-        if params.verbose {
-            println!("Player {} made {:?}", playing, action);
-        }
+        let turn_over = play_action(mapf, actors, &mut state, record, params.verbose);
 
         if iteration % 1000 == 0 {
             println!("Iteration {}", iteration);
         }
 
-        if let MAPFAction::Commit = action {
-            let status = mapf.get_status(&state);
+        if turn_over {
+            let outcome = mapf.get_outcome(&state);
 
             if params.verbose {
+                if !state.clashes.is_empty() {
+                    println!("Clashes: {:?}", state.clashes);
+                }
                 println!("Iteration {}:\n{}\n\n", iteration, state);
             }
 
-            if let StateStatus::Winner(w) = status {
-                return Ok(w);
+            if outcome.status != StateStatus::Running {
+                return Ok(outcome);
             }
         }
 
@@ -83,6 +135,55 @@ pub fn evaluate_ai(mapf: &MAPFEnvironment, mut actors: Vec<Box<dyn AI>>, params:
     Err(Box::new(NotFinished{}))
 }
 
+/// Plays every variant of `MAPFEnvironment::variants`, with fresh actors from `actors` for every game. Actors
+/// keep their player id, so half of the games are played from the other side of the map.
+pub fn evaluate_variants<F>(mapf: &MAPFEnvironment, actors: F, params: EvaluateAIParams) -> Vec<Result<Outcome, Box<dyn Error>>>
+where
+    F: Fn() -> Vec<Box<dyn AI>>,
+{
+    mapf.variants()
+        .iter()
+        .map(|variant| evaluate_ai(variant, actors(), params.clone()))
+        .collect()
+}
+
+/// Lets the player to move choose an action and plays it, recording it in `record`. Returns whether the turn
+/// is over.
+pub(crate) fn play_action(
+    mapf: &MAPFEnvironment,
+    actors: &mut [Box<dyn AI>],
+    state: &mut MAPFState,
+    record: &mut GameRecord,
+    verbose: bool,
+) -> bool {
+    let playing = state.playing;
+    let actor = mapf.definition.players.iter().position(|&p| p == playing).unwrap();
+    let thinking = Instant::now();
+    let action = actors[actor].next(&mapf.observe(state, playing), mapf);
+    let elapsed = thinking.elapsed();
+
+    if verbose {
+        println!("Player {} made {:?}", playing, action);
+    }
+
+    match mapf.try_next(state, &action) {
+        Ok(next) => {
+            *state = next;
+            record.entries.push(RecordEntry { player: playing, action: Some(action.clone()), elapsed });
+            action == MAPFAction::Commit
+        }
+        Err(e) => {
+            // Misbehaving AIs forfeit instead of corrupting the game
+            if verbose {
+                println!("Player {} forfeits: {}", playing, e);
+            }
+            *state = mapf.forfeit(state);
+            record.entries.push(RecordEntry { player: playing, action: None, elapsed });
+            true
+        }
+    }
+}
+
 pub struct GatherArgs {
     pub loops: u64,
 }
@@ -95,11 +196,11 @@ impl Default for GatherArgs {
     }
 }
 
-pub fn gather<F>(f: F, args: GatherArgs) -> Vec<Result<u64, Box<dyn Error>>>
+pub fn gather<T, F>(f: F, args: GatherArgs) -> Vec<Result<T, Box<dyn Error>>>
 where
-    F: Fn() -> Result<u64, Box<dyn Error>>,
+    F: Fn() -> Result<T, Box<dyn Error>>,
 {
-    let mut results: Vec<Result<u64, Box<dyn Error>>> = vec![];
+    let mut results: Vec<Result<T, Box<dyn Error>>> = vec![];
 
     for _epoch in 0..args.loops {
         results.push(f());
@@ -5,13 +5,51 @@ use crate::ai::greedy::GreedyAI;
 use crate::ai::random_ai::RandomAI;
 use crate::loops::{args, evaluate_ai, EvaluateAIParams};
 use crate::mapf::environment::MAPFEnvironment;
+use crate::mapf::generator::{generate_suite, GeneratorParams, Layout};
+use crate::mapf::record::GameRecord;
+use crate::viewer::Replay;
 
 mod deps;
 mod mapf;
 mod ai;
 pub mod loops;
+mod viewer;
 
 fn main() {
+    // `view <record>` replays a recorded game, `play <map>` watches a live game between the default AIs and
+    // `generate <dir> <count> [random|maze|rooms|arena]` writes a suite of generated maps
+    let args: Vec<String> = std::env::args().collect();
+    match args.get(1).map(String::as_str) {
+        Some("view") => {
+            let record = GameRecord::load_from_file(&args[2]).unwrap();
+            viewer::run(Replay::from_record(record).unwrap()).unwrap();
+            return;
+        }
+        Some("play") => {
+            let env = MAPFEnvironment::new_from_file(&args[2]).unwrap();
+            viewer::run(Replay::live(env, vec![Box::new(GreedyAI::new()), Box::new(RandomAI::new())]).unwrap()).unwrap();
+            return;
+        }
+        Some("generate") => {
+            let layout = match args.get(4).map(String::as_str) {
+                Some("maze") => Layout::Maze,
+                Some("rooms") => Layout::Rooms { rooms: 4 },
+                Some("arena") => Layout::Arena { density: 0.2 },
+                _ => Layout::Random { density: 0.2 },
+            };
+            let params = GeneratorParams { layout, ..GeneratorParams::default() };
+
+            for (seed, env) in generate_suite(params, args[3].parse().unwrap()).into_iter().enumerate() {
+                match env {
+                    Ok(env) => env.save_to_file(format!("{}/generated-{}.txt", args[2], seed)).unwrap(),
+                    Err(e) => println!("Seed {}: {}", seed, e),
+                }
+            }
+            return;
+        }
+        _ => {}
+    }
+
     println!("Hello, world!");
 
     let env = MAPFEnvironment::new_from_file("./maps/defender.txt").unwrap();
@@ -1,7 +1,61 @@
 pub const MOVES: [(isize, isize); 4] = [(0, 1), (1, 0), (0, -1), (-1, 0)];
+pub const DIAGONAL_MOVES: [(isize, isize); 4] = [(1, 1), (1, -1), (-1, -1), (-1, 1)];
+pub const KNIGHT_MOVES: [(isize, isize); 8] = [(1, 2), (2, 1), (2, -1), (1, -2), (-1, -2), (-2, -1), (-2, 1), (-1, 2)];
 
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum MAPFAction {
     Commit,
     Move((usize, usize), (usize, usize)),
-}
\ No newline at end of file
+}
+
+/// A complete turn of the player to move: at most one `Move` per unit, the closing `Commit` is implied
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct JointTurn {
+    pub actions: Vec<MAPFAction>,
+}
+
+/// A single unit move made by `player` during the current round. `agent` is the id of the moved unit
+/// in labelled mode, 0 otherwise.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct UnitMove {
+    pub player: u8,
+    pub agent: u16,
+    pub from: (usize, usize),
+    pub to: (usize, usize),
+}
+
+/// The neighbourhood a unit can move to in a single step
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MoveSet {
+    pub offsets: Vec<(isize, isize)>,
+    /// Whether diagonal steps may cut past obstacles on either of their corners
+    pub corner_cutting: bool,
+    /// Whether units may explicitly wait, offered as a move onto the unit's own cell
+    pub waits: bool,
+}
+
+impl MoveSet {
+    pub fn four_connected() -> Self {
+        MoveSet { offsets: MOVES.to_vec(), corner_cutting: false, waits: false }
+    }
+
+    pub fn eight_connected(corner_cutting: bool) -> Self {
+        MoveSet { offsets: [MOVES, DIAGONAL_MOVES].concat(), corner_cutting, waits: false }
+    }
+
+    /// Arbitrary offsets, such as `KNIGHT_MOVES`. Only steps of length one in both axes are treated as diagonal.
+    pub fn custom(offsets: Vec<(isize, isize)>) -> Self {
+        MoveSet { offsets, corner_cutting: true, waits: false }
+    }
+
+    pub fn with_waits(mut self, waits: bool) -> Self {
+        self.waits = waits;
+        self
+    }
+}
+
+impl Default for MoveSet {
+    fn default() -> Self {
+        Self::four_connected()
+    }
+}
@@ -0,0 +1,196 @@
+/// How a cell contested by units of different players is resolved
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+pub enum CombatRule {
+    /// Every unit in the cell is destroyed
+    #[default]
+    MutualDestruction,
+    /// The unit that stayed in the cell is destroyed; a single attacker takes the cell, several destroy each other
+    AttackerWins,
+    /// The unit that stayed in the cell survives and every attacker is destroyed; without one all units are destroyed
+    DefenderWins,
+    /// The player with strictly the most units in the cell keeps one of them, on a tie all units are destroyed
+    NumericMajority,
+}
+
+/// How units of the same player running into each other are treated
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+pub enum FriendlyCollisions {
+    /// All of the colliding units are destroyed. This includes two own units swapping cells through each other,
+    /// recorded as a `Clash::Edge` with winner 0.
+    #[default]
+    Destroy,
+    /// Moves onto a cell held by an own unit are not offered by `get_actions`, which also rules out swaps
+    Illegal,
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+pub struct CollisionRules {
+    pub combat: CombatRule,
+    pub friendly: FriendlyCollisions,
+}
+
+/// A collision that happened while a round was resolved. `winner` is the player whose unit survived, 0 if none did.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Clash {
+    /// Units ended the round in the same cell
+    Vertex { cell: (usize, usize), defender: u8, attackers: Vec<u8>, winner: u8 },
+    /// Two units swapped cells, passing through each other
+    Edge { cells: ((usize, usize), (usize, usize)), players: (u8, u8), winner: u8 },
+}
+
+impl CollisionRules {
+    pub fn new(combat: CombatRule, friendly: FriendlyCollisions) -> Self {
+        CollisionRules { combat, friendly }
+    }
+
+    /// Resolves a single cell at the end of a round. `defender` is the player whose unit stayed in the cell
+    /// (0 if none), `attackers` are the players whose units moved into it. Returns the player holding the cell
+    /// afterwards, 0 if the cell ends up empty.
+    pub fn resolve(&self, defender: u8, attackers: &[u8]) -> u8 {
+        let mut occupants: Vec<u8> = attackers.to_vec();
+        if defender != 0 {
+            occupants.push(defender);
+        }
+
+        match occupants.as_slice() {
+            [] => return 0,
+            [single] => return *single,
+            _ => {}
+        }
+
+        if occupants.iter().all(|&p| p == occupants[0]) {
+            // Friendly collision, only reachable when it is not prevented by `FriendlyCollisions::Illegal`
+            return 0;
+        }
+
+        match self.combat {
+            CombatRule::MutualDestruction => 0,
+            CombatRule::AttackerWins => {
+                if defender == 0 {
+                    0
+                } else {
+                    self.resolve(0, attackers)
+                }
+            }
+            CombatRule::DefenderWins => defender,
+            CombatRule::NumericMajority => {
+                let mut counts: Vec<(u8, usize)> = Vec::new();
+                for &p in &occupants {
+                    match counts.iter_mut().find(|(player, _)| *player == p) {
+                        Some((_, count)) => *count += 1,
+                        None => counts.push((p, 1)),
+                    }
+                }
+
+                counts.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+
+                if counts[0].1 > counts[1].1 { counts[0].0 } else { 0 }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::deps::state_definition::StateEnvironment;
+    use crate::mapf::action::MAPFAction;
+    use crate::mapf::collision::{CollisionRules, CombatRule, FriendlyCollisions};
+    use crate::mapf::environment::MAPFEnvironment;
+    use crate::mapf::state::MAPFState;
+
+    fn rules(combat: CombatRule) -> CollisionRules {
+        CollisionRules::new(combat, FriendlyCollisions::Destroy)
+    }
+
+    /// Player 1 walks into the stationary unit of player 2
+    fn attack(combat: CombatRule) -> MAPFState {
+        let env = MAPFEnvironment::new_from_file("./maps/test_duel.test.txt")
+            .unwrap()
+            .with_collision_rules(rules(combat));
+        let mut state = env.get_initial_state();
+
+        state = env.next(&state, &MAPFAction::Move((0, 1), (0, 2)));
+        state = env.next(&state, &MAPFAction::Commit);
+        state = env.next(&state, &MAPFAction::Commit);
+        state = env.next(&state, &MAPFAction::Move((0, 2), (0, 3)));
+        env.next(&state, &MAPFAction::Commit)
+    }
+
+    #[test]
+    fn test_uncontested() {
+        for combat in [CombatRule::MutualDestruction, CombatRule::AttackerWins, CombatRule::DefenderWins, CombatRule::NumericMajority] {
+            assert_eq!(rules(combat).resolve(0, &[]), 0);
+            assert_eq!(rules(combat).resolve(2, &[]), 2);
+            assert_eq!(rules(combat).resolve(0, &[1]), 1);
+        }
+    }
+
+    #[test]
+    fn test_mutual_destruction() {
+        let r = rules(CombatRule::MutualDestruction);
+        assert_eq!(r.resolve(2, &[1]), 0);
+        assert_eq!(r.resolve(0, &[1, 2]), 0);
+        assert_eq!(r.resolve(2, &[1, 1]), 0);
+
+        let state = attack(CombatRule::MutualDestruction);
+        assert_eq!(state.units_begin.get_nnz_sum(), 0);
+    }
+
+    #[test]
+    fn test_attacker_wins() {
+        let r = rules(CombatRule::AttackerWins);
+        assert_eq!(r.resolve(2, &[1]), 1);
+        assert_eq!(r.resolve(0, &[1, 2]), 0);
+        assert_eq!(r.resolve(3, &[1, 2]), 0);
+        assert_eq!(r.resolve(2, &[1, 1]), 0);
+
+        let state = attack(CombatRule::AttackerWins);
+        assert_eq!(state.units_begin.get_nnz(), vec![(0, 3, 1)]);
+    }
+
+    #[test]
+    fn test_defender_wins() {
+        let r = rules(CombatRule::DefenderWins);
+        assert_eq!(r.resolve(2, &[1]), 2);
+        assert_eq!(r.resolve(2, &[1, 3]), 2);
+        assert_eq!(r.resolve(0, &[1, 2]), 0);
+
+        let state = attack(CombatRule::DefenderWins);
+        assert_eq!(state.units_begin.get_nnz(), vec![(0, 3, 2)]);
+    }
+
+    #[test]
+    fn test_numeric_majority() {
+        let r = rules(CombatRule::NumericMajority);
+        assert_eq!(r.resolve(2, &[1]), 0);
+        assert_eq!(r.resolve(2, &[1, 1]), 1);
+        assert_eq!(r.resolve(1, &[2, 2, 3]), 2);
+        assert_eq!(r.resolve(0, &[1, 1, 2, 2]), 0);
+
+        let state = attack(CombatRule::NumericMajority);
+        assert_eq!(state.units_begin.get_nnz_sum(), 0);
+    }
+
+    #[test]
+    fn test_friendly_collisions() {
+        let destroy = rules(CombatRule::DefenderWins);
+        assert_eq!(destroy.resolve(1, &[1]), 0);
+        assert_eq!(destroy.resolve(0, &[1, 1]), 0);
+
+        // Default environment has player 1 units stacked at (3, 0), (4, 0) and (5, 0)
+        let env = MAPFEnvironment::new();
+        let state = env.get_initial_state();
+        assert!(env.get_actions(&state).contains(&MAPFAction::Move((3, 0), (4, 0))));
+
+        let env = MAPFEnvironment::new()
+            .with_collision_rules(CollisionRules::new(CombatRule::MutualDestruction, FriendlyCollisions::Illegal));
+        let state = env.get_initial_state();
+        assert!(!env.get_actions(&state).contains(&MAPFAction::Move((3, 0), (4, 0))));
+
+        // Once the unit has moved away its cell is free, cells of units that stayed are not
+        let state = env.next(&state, &MAPFAction::Move((3, 0), (2, 0)));
+        let actions = env.get_actions(&state);
+        assert!(actions.contains(&MAPFAction::Move((4, 0), (3, 0))));
+        assert!(!actions.contains(&MAPFAction::Move((5, 0), (4, 0))));
+    }
+}
@@ -1,11 +1,236 @@
 use crate::deps::sparse::SparseMatrix2D;
+use crate::mapf::action::MoveSet;
+use std::collections::VecDeque;
 
+/// Terrain symbols of the text map format and the cost of entering them, a plain `.` cell costs 1
+pub const TERRAIN: [(char, u8); 2] = [(',', 2), ('~', 3)];
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct MAPFDefinition {
     pub shape: (usize, usize),
     pub starting_positions: SparseMatrix2D,
     pub obstacles: SparseMatrix2D,
     pub goals: SparseMatrix2D,
-    pub goals_num: [u64; 3],
-}
\ No newline at end of file
+    /// Number of goals per player, indexed by player id (index 0 is unused)
+    pub goals_num: Vec<u64>,
+    /// Players present on the map, in turn order
+    pub players: Vec<u8>,
+    pub limits: GameLimits,
+    pub move_set: MoveSet,
+    /// Cost of entering every cell in movement points, 0 stands for the plain cost of 1
+    pub costs: SparseMatrix2D,
+    /// Terrain symbols of the map and their costs, `TERRAIN` unless overridden by the map header
+    pub terrain: Vec<(char, u8)>,
+    /// Movement points every unit gains per turn
+    pub movement_points: u8,
+    /// How far every unit sees, `None` when the whole map is visible
+    pub vision: Option<u32>,
+    pub metadata: MapMetadata,
+    /// Labelled agents, agent `id` is stored at index `id - 1`. Empty when units are anonymous.
+    pub agents: Vec<Agent>,
+}
+
+impl MAPFDefinition {
+    pub fn goals_of(&self, player: u8) -> u64 {
+        self.goals_num.get(player as usize).copied().unwrap_or(0)
+    }
+
+    pub fn cost(&self, cell: (usize, usize)) -> u8 {
+        self.costs.get(cell.0, cell.1).unwrap_or(0).max(1)
+    }
+
+    /// Movement points a unit can save up, always enough to enter the most expensive cell of the map
+    pub fn max_points(&self) -> u8 {
+        self.costs.get_nnz().iter()
+            .map(|&(_, _, cost)| cost)
+            .max()
+            .unwrap_or(1)
+            .max(self.movement_points)
+    }
+
+    /// Symbol printed for cells of `cost`, the map header keeps a single symbol per cost
+    pub fn terrain_symbol(&self, cost: u8) -> Option<char> {
+        self.terrain.iter().find(|&&(_, c)| c == cost).map(|&(symbol, _)| symbol)
+    }
+
+    fn is_free(&self, cell: (isize, isize)) -> bool {
+        cell.0 >= 0 && cell.1 >= 0
+            && (cell.0 as usize) < self.shape.0 && (cell.1 as usize) < self.shape.1
+            && self.obstacles.get(cell.0 as usize, cell.1 as usize).unwrap_or(0) == 0
+    }
+
+    /// Target of a single step by `offset` from `from`, if the move set allows it
+    fn step(&self, from: (usize, usize), offset: (isize, isize)) -> Option<(usize, usize)> {
+        let (f0, f1) = (from.0 as isize, from.1 as isize);
+        let target = (f0 + offset.0, f1 + offset.1);
+
+        if !self.is_free(target) {
+            return None;
+        }
+
+        let diagonal = offset.0.abs() == 1 && offset.1.abs() == 1;
+        if diagonal && !self.move_set.corner_cutting
+            && (!self.is_free((f0 + offset.0, f1)) || !self.is_free((f0, f1 + offset.1)))
+        {
+            return None;
+        }
+
+        Some((target.0 as usize, target.1 as usize))
+    }
+
+    /// Cells a unit standing on `from` can move to, including `from` itself when waiting is allowed
+    pub fn neighbours(&self, from: (usize, usize)) -> Vec<(usize, usize)> {
+        let mut cells: Vec<(usize, usize)> = self.move_set.offsets.iter()
+            .filter_map(|&offset| self.step(from, offset))
+            .collect();
+
+        if self.move_set.waits {
+            cells.push(from);
+        }
+
+        cells
+    }
+
+    /// Cells from which a unit can move to `to` in a single step, not counting waits
+    pub fn predecessors(&self, to: (usize, usize)) -> Vec<(usize, usize)> {
+        self.move_set.offsets.iter()
+            .filter_map(|&(d0, d1)| {
+                let from = (to.0 as isize - d0, to.1 as isize - d1);
+                if !self.is_free(from) {
+                    return None;
+                }
+
+                let from = (from.0 as usize, from.1 as usize);
+                (self.step(from, (d0, d1)) == Some(to)).then_some(from)
+            })
+            .collect()
+    }
+
+    /// Fewest steps from `from` to every cell, ignoring units and terrain costs, indexed as `[a0][a1]`. `None`
+    /// marks cells that can not be reached.
+    pub fn step_distances(&self, from: (usize, usize)) -> Vec<Vec<Option<u32>>> {
+        let mut distances = vec![vec![None; self.shape.1]; self.shape.0];
+        let mut queue = VecDeque::from([from]);
+        distances[from.0][from.1] = Some(0);
+
+        while let Some(cell) = queue.pop_front() {
+            let distance = distances[cell.0][cell.1].unwrap();
+
+            for next in self.neighbours(cell) {
+                if distances[next.0][next.1].is_none() {
+                    distances[next.0][next.1] = Some(distance + 1);
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        distances
+    }
+
+    /// Steps from every unit to the closest goal of its player, ignoring the other units, grouped by player id.
+    /// In labelled mode only the agent's own goal counts.
+    pub fn goal_distances(&self) -> Vec<Vec<Option<u32>>> {
+        let goals = self.goals.get_nnz();
+        let mut per_player = vec![Vec::new(); self.goals_num.len()];
+
+        for (a0, a1, player) in self.starting_positions.get_nnz() {
+            let distances = self.step_distances((a0, a1));
+            let closest = goals.iter()
+                .filter(|&&(g0, g1, owner)| match self.agents.iter().find(|agent| agent.start == (a0, a1)) {
+                    Some(agent) => agent.goal == (g0, g1),
+                    None => owner == player,
+                })
+                .filter_map(|&(g0, g1, _)| distances[g0][g1])
+                .min();
+
+            per_player[player as usize].push(closest);
+        }
+
+        per_player
+    }
+
+    /// Whether no obstacle lies on the straight line between `from` and `to`, not counting the two cells
+    pub fn line_of_sight(&self, from: (usize, usize), to: (usize, usize)) -> bool {
+        let (mut x, mut y) = (from.0 as isize, from.1 as isize);
+        let (x1, y1) = (to.0 as isize, to.1 as isize);
+        let (dx, dy) = ((x1 - x).abs(), -(y1 - y).abs());
+        let (sx, sy) = (if x < x1 { 1 } else { -1 }, if y < y1 { 1 } else { -1 });
+        let mut err = dx + dy;
+
+        loop {
+            if (x, y) == (x1, y1) {
+                return true;
+            }
+
+            if (x as usize, y as usize) != from && self.obstacles.get(x as usize, y as usize).unwrap_or(0) != 0 {
+                return false;
+            }
+
+            let e2 = 2 * err;
+            if e2 >= dy {
+                err += dy;
+                x += sx;
+            }
+            if e2 <= dx {
+                err += dx;
+                y += sy;
+            }
+        }
+    }
+
+    pub fn is_labelled(&self) -> bool {
+        !self.agents.is_empty()
+    }
+
+    pub fn agent(&self, id: u16) -> Option<&Agent> {
+        (id as usize).checked_sub(1).and_then(|idx| self.agents.get(idx))
+    }
+
+    /// Whether a unit of `player` standing on `cell` is on its goal. In labelled mode only the goal of
+    /// the unit's own `agent` counts, otherwise any goal of the player does.
+    pub fn is_goal_of(&self, cell: (usize, usize), player: u8, agent: u16) -> bool {
+        if self.is_labelled() {
+            return self.agent(agent).is_some_and(|a| a.player == player && a.goal == cell);
+        }
+
+        self.goals.get(cell.0, cell.1) == Some(player)
+    }
+}
+
+/// A labelled unit that has to reach its own goal cell, as in classic MAPF
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Agent {
+    pub player: u8,
+    pub start: (usize, usize),
+    pub goal: (usize, usize),
+}
+
+/// Descriptive fields of a map file, they have no effect on the game
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MapMetadata {
+    pub name: Option<String>,
+    pub author: Option<String>,
+    /// What the map is about, kept as written in the map file
+    pub description: Option<String>,
+}
+
+/// Rules ending games that would otherwise never finish. A round is over once every player has committed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GameLimits {
+    /// Maximum number of rounds played
+    pub turn_limit: Option<u32>,
+    /// Maximum number of consecutive rounds in which no unit reached an own goal and no unit was destroyed
+    pub no_progress_limit: Option<u32>,
+    /// How many times the same position may occur at the start of a turn
+    pub repetition_limit: Option<u32>,
+    pub tiebreak: Tiebreak,
+}
+
+/// Outcome of a game stopped by one of the `GameLimits`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Tiebreak {
+    #[default]
+    Draw,
+    /// The player holding the most own goals wins, equal holdings are a draw
+    GoalsHeld,
+}
@@ -1,28 +1,85 @@
 use crate::deps::sparse::SparseMatrix2D;
-use crate::deps::state_definition::{StateEnvironment, StateStatus};
+use crate::deps::state_definition::{FallibleStateEnvironment, StateEnvironment, StateStatus};
 use crate::mapf::action::MAPFAction::{Commit, Move};
-use crate::mapf::action::{MAPFAction, MOVES};
-use crate::mapf::definition::MAPFDefinition;
-use crate::mapf::environment::MAPFErrorLoad::{EmptyGrid, InconsistentGrid, InvalidCharacter};
-use crate::mapf::state::MAPFState;
-use crate::mapf::state::SpecialState::{AnyGoal, No};
-use std::{fmt::{self, Formatter}, fs, io, path::Path, sync::Arc};
+use crate::mapf::action::{MAPFAction, MoveSet, UnitMove};
+use crate::mapf::collision::{Clash, CollisionRules, FriendlyCollisions};
+use crate::mapf::definition::{Agent, GameLimits, MAPFDefinition, MapMetadata, Tiebreak, TERRAIN};
+use crate::mapf::environment::MAPFErrorLoad::{
+    EmptyGrid, InconsistentGrid, InvalidAgent, InvalidCharacter, InvalidHeader, UnmatchedGoals,
+};
+use crate::mapf::outcome::{Outcome, TerminationReason};
+use crate::mapf::outcome::TerminationReason::{
+    AllGoalsReached, Elimination, MutualElimination, NoGoalsLeft, NoProgress, Repetition, TurnLimit,
+};
+use crate::mapf::state::{CommitUndo, History, MAPFState, SavedCell, Undo};
+use crate::mapf::state::SpecialState::{self, AnyGoal, No};
+use crate::mapf::zobrist::{self, Stage};
+use std::{collections::BTreeMap, fmt::{self, Formatter}, fs, io, path::Path, sync::Arc};
 use thiserror::Error;
 
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum TurnMode {
+    /// Each player moves and commits in turn, reacting to the committed positions of the previous players
+    #[default]
+    Alternating,
+    /// Every player commits a full turn without seeing the others; the round is resolved once the last
+    /// player commits
+    Simultaneous,
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum MoveOrdering {
+    /// Units move in any order and the player commits whenever it likes
+    #[default]
+    Free,
+    /// Units move or skip, as a move onto their own cell, one after another in reading order and the player
+    /// commits once every unit is done. Every turn is then reached by exactly one sequence of actions, as in
+    /// Operator Decomposition.
+    Canonical,
+}
+
+#[derive(Clone)]
 pub struct MAPFEnvironment {
     pub definition: Arc<MAPFDefinition>,
+    pub turn_mode: TurnMode,
+    pub move_ordering: MoveOrdering,
+    pub collision_rules: CollisionRules,
 }
 
 impl StateEnvironment<MAPFState, MAPFAction> for MAPFEnvironment {
     fn get_initial_state(&self) -> MAPFState {
-        MAPFState {
+        let playing = self.definition.players.first().copied().unwrap_or(1);
+
+        let mut labels = SparseMatrix2D::new_by_shape(self.definition.shape);
+        for (idx, agent) in self.definition.agents.iter().enumerate() {
+            labels.insert(agent.start.0, agent.start.1, idx as u16 + 1);
+        }
+
+        let mut points = SparseMatrix2D::new_by_shape(self.definition.shape);
+        for (a0, a1, _) in self.definition.starting_positions.get_nnz() {
+            points.insert(a0, a1, self.definition.movement_points);
+        }
+
+        let mut state = MAPFState {
             definition: self.definition.clone(),
             units_begin: self.definition.starting_positions.clone(),
             units_available: self.definition.starting_positions.clone(),
             units_moved: SparseMatrix2D::new(self.definition.shape.0, self.definition.shape.1),
-            playing: 1,
+            history: History::default(),
+            zobrist: 0,
+            labels,
+            points,
+            playing,
+            moves: Vec::new(),
+            clashes: Vec::new(),
+            round: 0,
+            last_progress: 0,
             special_state: No,
-        }
+        };
+
+        state.zobrist = state.compute_zobrist();
+        state.history.push(state.zobrist);
+        state
     }
 
     fn get_actions(&self, state: &MAPFState) -> Arc<Vec<MAPFAction>> {
@@ -32,6 +89,7 @@ impl StateEnvironment<MAPFState, MAPFAction> for MAPFEnvironment {
         }
 
         let mut actions = Vec::new();
+        let in_order = self.next_in_order(state);
 
         for (a0_idx, vec_) in state.units_available.data.iter().enumerate() {
             if let Some(vec) = vec_ {
@@ -40,24 +98,23 @@ impl StateEnvironment<MAPFState, MAPFAction> for MAPFEnvironment {
                         continue
                     }
 
-                    for (da0, da1) in MOVES {
-                        let na0_ = (da0) + (a0_idx as isize);
-                        let na1_ = (da1) + (a1_idx as isize);
+                    if self.move_ordering == MoveOrdering::Canonical && in_order != Some((a0_idx, a1_idx)) {
+                        continue
+                    }
 
-                        if na0_ < 0 || na0_ >= self.definition.shape.0 as isize {
-                            continue;
-                        }
+                    let points = state.points.get(a0_idx, a1_idx).unwrap_or(0);
+
+                    for (na0, na1) in self.definition.neighbours((a0_idx, a1_idx)) {
+                        let wait = (na0, na1) == (a0_idx, a1_idx);
 
-                        if na1_ < 0 || na1_ >= self.definition.shape.1 as isize {
+                        if !wait && points < self.definition.cost((na0, na1)) {
                             continue;
                         }
 
-                        let na0 = na0_ as usize;
-                        let na1 = na1_ as usize;
-
-                        let obstacle = self.definition.obstacles.get(na0, na1);
-
-                        if obstacle.unwrap_or(0) != 0 {
+                        if !wait && self.collision_rules.friendly == FriendlyCollisions::Illegal
+                            && (state.units_available.get(na0, na1).unwrap_or(0) == state.playing
+                                || state.units_moved.get(na0, na1).unwrap_or(0) == state.playing)
+                        {
                             continue;
                         }
 
@@ -67,7 +124,14 @@ impl StateEnvironment<MAPFState, MAPFAction> for MAPFEnvironment {
             }
         }
 
-        actions.push(Commit);
+        match in_order {
+            Some(unit) if self.move_ordering == MoveOrdering::Canonical => {
+                if !actions.contains(&Move(unit, unit)) {
+                    actions.push(Move(unit, unit));
+                }
+            }
+            _ => actions.push(Commit),
+        }
 
         Arc::new(actions)
     }
@@ -78,89 +142,34 @@ impl StateEnvironment<MAPFState, MAPFAction> for MAPFEnvironment {
             panic!("Invoked next() on special state");
         }
 
-        match a {
-            Commit => {
-                let mut next_starting_pos = s.units_available.xor(&s.units_moved);
-                next_starting_pos.bit_reduce_inline();
-                MAPFState {
-                    definition: s.definition.clone(),
-                    units_begin: next_starting_pos.clone(),
-                    units_available: next_starting_pos,
-                    units_moved: SparseMatrix2D::new_by_shape(self.definition.shape),
-                    playing: if s.playing == 1 { 2 } else { 1 },
-                    special_state: No,
-                }
-            }
-            Move(stage1, stage2) => {
-                let mut stage1_positions = s.units_available.clone();
-                stage1_positions.xor_inline_by_idx(stage1.0, stage1.1, s.playing);
-
-                let mut stage2_positions = s.units_moved.clone();
-                stage2_positions.xor_inline_by_idx(stage2.0, stage2.1, s.playing);
-
-                MAPFState {
-                    definition: self.definition.clone(),
-                    units_begin: s.units_begin.clone(),
-                    units_available: stage1_positions,
-                    units_moved: stage2_positions,
-                    playing: s.playing,
-                    special_state: No,
-                }
-            }
-        }
+        let mut state = s.clone();
+        self.apply(&mut state, a).unwrap();
+        state
     }
 
     fn get_status(&self, s: &MAPFState) -> StateStatus {
+        self.get_outcome(s).status
+    }
 
-        if s.special_state != No {
-            panic!("Invoked get_status on special state")
-        }
-
-        if s.units_moved.get_nnz_sum() != 0 {
-            // If we're in the middle of a turn, the game is still running
-            return StateStatus::Running;
-        }
-
-        let mut alive: [u64; 3] = [0, 0, 0];
-        let mut goals_achieved: [u64; 3] = [0, 0, 0];
-
-        for (a0_index, row_opt) in s.units_begin.data.iter().enumerate() {
-            if let Some(row) = row_opt {
-                for (a1_index, &value) in row.iter().enumerate() {
-                    alive[value as usize] += 1;
-
-                    if s.definition.goals.get(a0_index, a1_index).unwrap_or(0) != 0 {
-                        goals_achieved[value as usize] += 1;
-                    }
-                }
-            }
-        }
-
-        // Check for winners
-        for i in 1..3 {  // Start from 1 to skip the 0 index (no player)
-            if goals_achieved[i] == s.definition.goals_num[i] && s.definition.goals_num[i] > 0 {
-                return StateStatus::Winner(i as u64);
-            }
-        }
-
-        if alive[1] == 0 {
-            return StateStatus::Winner(2);
-        }
-
-        if alive[2] == 0 {
-            return StateStatus::Winner(1);
-        }
+}
 
-        let player1_can_win = goals_achieved[1] < s.definition.goals_num[1] && alive[1] > 0;
-        let player2_can_win = goals_achieved[2] < s.definition.goals_num[2] && alive[2] > 0;
+impl FallibleStateEnvironment<MAPFState, MAPFAction> for MAPFEnvironment {
+    type Error = IllegalAction;
 
-        if !player1_can_win && !player2_can_win {
-            return StateStatus::Draw;
-        }
+    fn try_get_actions(&self, s: &MAPFState) -> Result<Arc<Vec<MAPFAction>>, IllegalAction> {
+        Self::check_special_state(s)?;
+        Ok(self.get_actions(s))
+    }
 
-        StateStatus::Running
+    /// Like `next`, but refuses actions the playing player is not allowed to take
+    fn try_next(&self, s: &MAPFState, a: &MAPFAction) -> Result<MAPFState, IllegalAction> {
+        self.validate_action(s, a)?;
+        Ok(self.next(s, a))
     }
 
+    fn try_get_status(&self, s: &MAPFState) -> Result<StateStatus, IllegalAction> {
+        Ok(self.try_get_outcome(s)?.status)
+    }
 }
 
 impl fmt::Display for MAPFEnvironment {
@@ -170,7 +179,8 @@ impl fmt::Display for MAPFEnvironment {
             starting_positions,
             obstacles,
             goals,
-            goals_num: _goals_num,
+            costs,
+            ..
         } = &*self.definition;
 
         for y in 0..*height {
@@ -203,6 +213,13 @@ impl fmt::Display for MAPFEnvironment {
                     }
                 }
 
+                if ch == '.' {
+                    let cost = costs.get(y, x).unwrap_or(0);
+                    if cost > 1 {
+                        ch = self.definition.terrain_symbol(cost).unwrap_or('?');
+                    }
+                }
+
                 write!(f, "{}", ch)?;
             }
             writeln!(f)?;
@@ -220,36 +237,135 @@ pub enum MAPFErrorLoad {
     #[error("No Such file")]
     NoSuchFile(#[from] io::Error),
 
-    #[error("Inconsistent grid at {0}")]
+    #[error("Grid row on line {0} differs in width from the first row")]
     InconsistentGrid(usize),
 
-    #[error("Invalid character '{0}' at {1}, {2}")]
-    InvalidCharacter(char, usize, usize)
+    #[error("Invalid character '{0}' on line {1}, column {2}")]
+    InvalidCharacter(char, usize, usize),
+
+    #[error("{0} on line {1}, column {2}")]
+    Syntax(String, usize, usize),
+
+    #[error("Invalid header line {0}, expected 'key: value'")]
+    InvalidHeader(usize),
+
+    #[error("Unknown header key '{0}' on line {1}")]
+    UnknownHeaderKey(String, usize),
+
+    #[error("Invalid value for '{0}' on line {1}")]
+    InvalidHeaderValue(String, usize),
+
+    #[error("Invalid agent {0}")]
+    InvalidAgent(u16),
+
+    #[error("Player {0} does not have as many goals as units")]
+    UnmatchedGoals(u8),
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum IllegalAction {
+    #[error("Actions can not be taken in this state")]
+    WrongPhase,
+
+    #[error("{0} is a sentinel and not a playable state")]
+    SpecialState(SpecialState),
+
+    #[error("Cell {0:?} is out of bounds")]
+    OutOfBounds((usize, usize)),
+
+    #[error("No unit of the playing player at {0:?}")]
+    NotYourUnit((usize, usize)),
+
+    #[error("Unit at {0:?} has already moved this turn")]
+    UnitAlreadyMoved((usize, usize)),
+
+    #[error("Cell {1:?} is not reachable from {0:?} in a single move")]
+    NotAdjacent((usize, usize), (usize, usize)),
+
+    #[error("Cell {0:?} is an obstacle")]
+    Obstacle((usize, usize)),
+
+    #[error("Cell {0:?} is held by an own unit")]
+    FriendlyCollision((usize, usize)),
+
+    #[error("The unit at {0:?} has to move or skip first")]
+    OutOfOrder((usize, usize)),
+
+    #[error("Unit at {0:?} does not have the {1} movement points needed to enter {2:?}")]
+    NotEnoughPoints((usize, usize), u8, (usize, usize)),
 }
 
 impl MAPFEnvironment {
+    /// Loads a map, `.toml` files as structured maps and any other file in the text format
     pub fn new_from_file<P: AsRef<Path>>(path: P) -> Result<Self, MAPFErrorLoad> {
-        let content = fs::read_to_string(path)?;
+        let content = fs::read_to_string(&path)?;
+
+        match path.as_ref().extension().and_then(|extension| extension.to_str()) {
+            Some("toml") => Self::from_toml(&content),
+            _ => Self::from_text(&content),
+        }
+    }
+
+    /// Parses the text format, a grid optionally preceded by `key: value` header lines and a `---` separator.
+    /// Lines starting with `;` are comments, grid rows may also end in one.
+    pub fn from_text(content: &str) -> Result<Self, MAPFErrorLoad> {
+        let mut lines: Vec<(usize, &str)> = content.lines()
+            .enumerate()
+            .map(|(idx, line)| (idx + 1, line))
+            .filter(|(_, line)| !line.trim_start().starts_with(';'))
+            .collect();
+        let mut settings: Vec<(usize, &str, &str)> = Vec::new();
+
+        // Optional header of 'key: value' lines, separated from the grid by '---'
+        if let Some(separator) = lines.iter().position(|(_, line)| line.trim() == "---") {
+            for &(line_number, line) in &lines[..separator] {
+                if line.trim().is_empty() {
+                    continue;
+                }
+
+                let (key, value) = line.split_once(':').ok_or(InvalidHeader(line_number))?;
+                settings.push((line_number, key.trim(), value.trim()));
+            }
+
+            lines.drain(..=separator);
+        }
+
+        let grid: Vec<(usize, usize, &str)> = lines.into_iter()
+            .map(|(line_number, line)| (line_number, 1, line))
+            .collect();
+
+        Self::from_grid(&grid, &settings)
+    }
+
+    /// Builds the environment from grid rows given as (line, column, text) and applies the settings, given
+    /// as (line, key, value), on top of it
+    pub(crate) fn from_grid(grid: &[(usize, usize, &str)], settings: &[(usize, &str, &str)]) -> Result<Self, MAPFErrorLoad> {
         let mut obstacles_data: Vec<Option<Vec<u8>>> = Vec::new();
         let mut starting_data: Vec<Option<Vec<u8>>> = Vec::new();
         let mut goals_data: Vec<Option<Vec<u8>>> = Vec::new();
-        let mut goals_by_player: [u64; 3] = [0, 0, 0];
-
-        let lines: Vec<&str> = content
-            .lines()
-            .filter(|line| !line.trim().is_empty())
+        let mut goals_by_player: Vec<u64> = vec![0];
+        let mut players: Vec<u8> = Vec::new();
+        // Terrain is resolved once the settings had a chance to define its symbols
+        let mut terrain_cells: Vec<(char, usize, usize, usize, usize)> = Vec::new();
+
+        let mut lines: Vec<(usize, usize, &str)> = grid.iter()
+            .map(|&(line_number, column, line)| match line.split_once(';') {
+                Some((row, _)) => (line_number, column, row.trim_end()),
+                None => (line_number, column, line),
+            })
             .collect();
+        lines.retain(|(_, _, line)| !line.trim().is_empty());
 
         if lines.is_empty() {
             return Err(EmptyGrid);
         }
 
-        let a1_length = lines[0].len();
+        let a1_length = lines[0].2.len();
         let a0_length = lines.len();
 
-        for (y, line) in lines.iter().enumerate() {
+        for (y, &(line_number, column, line)) in lines.iter().enumerate() {
             if line.len() != a1_length {
-                return Err(InconsistentGrid(line.len()));
+                return Err(InconsistentGrid(line_number));
             }
 
             let mut obstacle_row = vec![0u8; a1_length];
@@ -268,16 +384,19 @@ impl MAPFEnvironment {
                         has_obstacle = true;
                     }
                     '0'..='9' => {
-                        starting_row[x] = ch.to_digit(10).unwrap() as u8;
+                        let player = ch.to_digit(10).unwrap() as u8;
+                        starting_row[x] = player;
                         has_starting = true;
+
+                        if player != 0 && !players.contains(&player) {
+                            players.push(player);
+                        }
                     }
                     'A'..='Z' => {
                         goals_row[x] = (ch as u8 - b'A') + 1;
                         has_goals = true;
                     }
-                    _ => {
-                        return Err(InvalidCharacter(ch, x, y));
-                    }
+                    _ => terrain_cells.push((ch, x, y, line_number, column + x)),
                 }
             }
 
@@ -296,14 +415,29 @@ impl MAPFEnvironment {
 
         for vec_ in goals_data.iter() {
             if let Some(vec) = vec_ {
-                for goal_player in vec {
-                    goals_by_player[*goal_player as usize] += 1;
+                for &goal_player in vec {
+                    if goal_player == 0 {
+                        continue;
+                    }
+
+                    if goals_by_player.len() <= goal_player as usize {
+                        goals_by_player.resize(goal_player as usize + 1, 0);
+                    }
+
+                    goals_by_player[goal_player as usize] += 1;
                 }
             }
         }
 
-        Ok(Self {
-            definition: Arc::new(MAPFDefinition {
+        players.sort();
+
+        if let Some(&last) = players.last() {
+            if goals_by_player.len() <= last as usize {
+                goals_by_player.resize(last as usize + 1, 0);
+            }
+        }
+
+        let definition = MAPFDefinition {
                 shape: (a0_length, a1_length),
                 starting_positions: SparseMatrix2D {
                     data: starting_data,
@@ -318,12 +452,608 @@ impl MAPFEnvironment {
                     shape: (a0_length, a1_length),
                 },
                 goals_num: goals_by_player,
-            }),
-        })
+                players,
+                limits: GameLimits::default(),
+                move_set: MoveSet::default(),
+                costs: SparseMatrix2D::new(a0_length, a1_length),
+                terrain: TERRAIN.to_vec(),
+                movement_points: 1,
+                vision: None,
+                metadata: MapMetadata::default(),
+                agents: Vec::new(),
+        };
+
+        let mut env = Self {
+            definition: Arc::new(definition),
+            turn_mode: TurnMode::Alternating,
+            move_ordering: MoveOrdering::Free,
+            collision_rules: CollisionRules::default(),
+        };
+
+        for &(line_number, key, value) in settings {
+            env.apply_setting(key, value, line_number)?;
+        }
+
+        let definition = Arc::make_mut(&mut env.definition);
+        for (ch, x, y, line_number, column) in terrain_cells {
+            let cost = definition.terrain.iter()
+                .find(|&&(symbol, _)| symbol == ch)
+                .map(|&(_, cost)| cost)
+                .ok_or(InvalidCharacter(ch, line_number, column))?;
+            definition.costs.insert(y, x, cost);
+        }
+
+        Ok(env)
     }
 }
 
 impl MAPFEnvironment {
+    /// Switches to labelled mode, every agent gets its own start and goal replacing those of the map
+    pub fn with_agents(mut self, agents: Vec<Agent>) -> Result<Self, MAPFErrorLoad> {
+        let shape = self.definition.shape;
+        let mut starting_positions = SparseMatrix2D::new_by_shape(shape);
+        let mut goals = SparseMatrix2D::new_by_shape(shape);
+        let mut goals_num: Vec<u64> = vec![0];
+        let mut players: Vec<u8> = Vec::new();
+
+        for (idx, agent) in agents.iter().enumerate() {
+            let id = idx as u16 + 1;
+
+            for cell in [agent.start, agent.goal] {
+                if cell.0 >= shape.0 || cell.1 >= shape.1 || self.definition.obstacles.get(cell.0, cell.1).unwrap_or(0) != 0 {
+                    return Err(InvalidAgent(id));
+                }
+            }
+
+            if agent.player == 0
+                || starting_positions.get(agent.start.0, agent.start.1).unwrap_or(0) != 0
+                || goals.get(agent.goal.0, agent.goal.1).unwrap_or(0) != 0
+            {
+                return Err(InvalidAgent(id));
+            }
+
+            starting_positions.insert(agent.start.0, agent.start.1, agent.player);
+            goals.insert(agent.goal.0, agent.goal.1, agent.player);
+
+            if goals_num.len() <= agent.player as usize {
+                goals_num.resize(agent.player as usize + 1, 0);
+            }
+            goals_num[agent.player as usize] += 1;
+
+            if !players.contains(&agent.player) {
+                players.push(agent.player);
+            }
+        }
+
+        players.sort();
+
+        let definition = Arc::make_mut(&mut self.definition);
+        definition.starting_positions = starting_positions;
+        definition.goals = goals;
+        definition.goals_num = goals_num;
+        definition.players = players;
+        definition.agents = agents;
+
+        Ok(self)
+    }
+
+    /// Switches to labelled mode by pairing the units of every player with its goals in reading order
+    pub fn labelled(self) -> Result<Self, MAPFErrorLoad> {
+        let mut agents = Vec::new();
+
+        for &player in &self.definition.players {
+            let starts: Vec<(usize, usize, u8)> = self.definition.starting_positions.get_nnz()
+                .into_iter()
+                .filter(|&(_, _, p)| p == player)
+                .collect();
+            let goals: Vec<(usize, usize, u8)> = self.definition.goals.get_nnz()
+                .into_iter()
+                .filter(|&(_, _, p)| p == player)
+                .collect();
+
+            if starts.len() != goals.len() {
+                return Err(UnmatchedGoals(player));
+            }
+
+            for ((s0, s1, _), (g0, g1, _)) in starts.into_iter().zip(goals) {
+                agents.push(Agent { player, start: (s0, s1), goal: (g0, g1) });
+            }
+        }
+
+        self.with_agents(agents)
+    }
+
+    pub fn with_turn_mode(mut self, turn_mode: TurnMode) -> Self {
+        self.turn_mode = turn_mode;
+        self
+    }
+
+    pub fn get_outcome(&self, s: &MAPFState) -> Outcome {
+        self.try_get_outcome(s).unwrap_or_else(|_| panic!("Invoked get_outcome on special state"))
+    }
+
+    /// Like `get_outcome`, but reports sentinel states instead of panicking
+    pub fn try_get_outcome(&self, s: &MAPFState) -> Result<Outcome, IllegalAction> {
+        Self::check_special_state(s)?;
+
+        let players = s.definition.goals_num.len();
+        let mut alive: Vec<u64> = vec![0; players];
+        let mut goals_achieved: Vec<u64> = vec![0; players];
+
+        for (a0_index, row_opt) in s.units_begin.data.iter().enumerate() {
+            if let Some(row) = row_opt {
+                for (a1_index, &value) in row.iter().enumerate() {
+                    if value == 0 {
+                        continue;
+                    }
+
+                    alive[value as usize] += 1;
+
+                    let agent = s.labels.get(a0_index, a1_index).unwrap_or(0);
+                    if s.definition.is_goal_of((a0_index, a1_index), value, agent) {
+                        goals_achieved[value as usize] += 1;
+                    }
+                }
+            }
+        }
+
+        let mut outcome = Outcome {
+            status: StateStatus::Running,
+            reason: None,
+            round: s.round,
+            units: alive,
+            goals_held: goals_achieved,
+        };
+
+        if s.units_moved.get_nnz_sum() != 0 || !s.moves.is_empty() {
+            // If we're in the middle of a turn or round, the game is still running
+            return Ok(outcome);
+        }
+
+        let (status, reason) = self.decide(s, &outcome.units, &outcome.goals_held);
+        outcome.status = status;
+        outcome.reason = reason;
+        Ok(outcome)
+    }
+
+    fn decide(&self, s: &MAPFState, alive: &[u64], goals_achieved: &[u64]) -> (StateStatus, Option<TerminationReason>) {
+        // Check for winners, in turn order
+        for &p in &s.definition.players {
+            let i = p as usize;
+            if goals_achieved[i] == s.definition.goals_num[i] && s.definition.goals_num[i] > 0 {
+                return (StateStatus::Winner(i as u64), Some(AllGoalsReached));
+            }
+        }
+
+        let survivors: Vec<u8> = s.definition.players.iter()
+            .copied()
+            .filter(|&p| alive[p as usize] > 0)
+            .collect();
+
+        match survivors.as_slice() {
+            [] => return (StateStatus::Draw, Some(MutualElimination)),
+            [last] => return (StateStatus::Winner(*last as u64), Some(Elimination)),
+            _ => {}
+        }
+
+        let anyone_can_win = survivors.iter()
+            .any(|&p| goals_achieved[p as usize] < s.definition.goals_num[p as usize]);
+
+        if !anyone_can_win {
+            return (StateStatus::Draw, Some(NoGoalsLeft));
+        }
+
+        let limits = &s.definition.limits;
+        let stalemate = if limits.turn_limit.is_some_and(|limit| s.round >= limit) {
+            Some(TurnLimit)
+        } else if limits.no_progress_limit.is_some_and(|limit| s.idle_rounds() >= limit) {
+            Some(NoProgress)
+        } else if limits.repetition_limit.is_some_and(|limit| s.repetitions() >= limit) {
+            Some(Repetition)
+        } else {
+            None
+        };
+
+        if let Some(reason) = stalemate {
+            if limits.tiebreak == Tiebreak::GoalsHeld {
+                let most = survivors.iter().map(|&p| goals_achieved[p as usize]).max().unwrap_or(0);
+                let leaders: Vec<u8> = survivors.iter()
+                    .copied()
+                    .filter(|&p| goals_achieved[p as usize] == most)
+                    .collect();
+
+                if let [leader] = leaders.as_slice() {
+                    return (StateStatus::Winner(*leader as u64), Some(reason));
+                }
+            }
+
+            return (StateStatus::Draw, Some(reason));
+        }
+
+        (StateStatus::Running, None)
+    }
+
+    fn check_special_state(s: &MAPFState) -> Result<(), IllegalAction> {
+        match s.special_state {
+            No => Ok(()),
+            special => Err(IllegalAction::SpecialState(special)),
+        }
+    }
+
+    /// Whether `s` satisfies `goal`, which may be a sentinel such as `get_any_goal_state`: any goal is reached
+    /// once a player occupies all of its goals.
+    pub fn is_goal(&self, s: &MAPFState, goal: &MAPFState) -> bool {
+        match (s.special_state, goal.special_state) {
+            (No, AnyGoal) => self.get_outcome(s).reason == Some(AllGoalsReached),
+            _ => s == goal,
+        }
+    }
+
+    pub fn validate_action(&self, s: &MAPFState, a: &MAPFAction) -> Result<(), IllegalAction> {
+        if self.try_get_status(s)? != StateStatus::Running {
+            return Err(IllegalAction::WrongPhase);
+        }
+
+        let in_order = self.next_in_order(s).filter(|_| self.move_ordering == MoveOrdering::Canonical);
+
+        let (from, to) = match a {
+            Commit => return in_order.map_or(Ok(()), |unit| Err(IllegalAction::OutOfOrder(unit))),
+            Move(from, to) => (*from, *to),
+        };
+
+        for cell in [from, to] {
+            if cell.0 >= self.definition.shape.0 || cell.1 >= self.definition.shape.1 {
+                return Err(IllegalAction::OutOfBounds(cell));
+            }
+        }
+
+        if s.units_available.get(from.0, from.1).unwrap_or(0) != s.playing {
+            if s.units_moved.get(from.0, from.1).unwrap_or(0) == s.playing {
+                return Err(IllegalAction::UnitAlreadyMoved(from));
+            }
+
+            return Err(IllegalAction::NotYourUnit(from));
+        }
+
+        if in_order.is_some_and(|unit| unit != from) {
+            return Err(IllegalAction::OutOfOrder(in_order.unwrap()));
+        }
+
+        if self.definition.obstacles.get(to.0, to.1).unwrap_or(0) != 0 {
+            return Err(IllegalAction::Obstacle(to));
+        }
+
+        let skip = in_order.is_some() && from == to;
+        if !skip && !self.definition.neighbours(from).contains(&to) {
+            return Err(IllegalAction::NotAdjacent(from, to));
+        }
+
+        let cost = self.definition.cost(to);
+        if from != to && s.points.get(from.0, from.1).unwrap_or(0) < cost {
+            return Err(IllegalAction::NotEnoughPoints(from, cost, to));
+        }
+
+        if from != to && self.collision_rules.friendly == FriendlyCollisions::Illegal
+            && (s.units_available.get(to.0, to.1).unwrap_or(0) == s.playing
+                || s.units_moved.get(to.0, to.1).unwrap_or(0) == s.playing)
+        {
+            return Err(IllegalAction::FriendlyCollision(to));
+        }
+
+        Ok(())
+    }
+
+    /// In-place version of `next` returning what is needed to `undo` the action. A unit move only touches the
+    /// two cells involved, a commit the cells of the moves of the round and of the units gaining points. Sentinel
+    /// states are reported instead of changed.
+    pub fn apply(&self, s: &mut MAPFState, a: &MAPFAction) -> Result<Undo, IllegalAction> {
+        Self::check_special_state(s)?;
+
+        Ok(match a {
+            Commit => self.commit(s),
+            Move(from, to) => {
+                s.units_available.xor_inline_by_idx(from.0, from.1, s.playing);
+                s.units_moved.xor_inline_by_idx(to.0, to.1, s.playing);
+
+                let agent = s.labels.get(from.0, from.1).unwrap_or(0);
+                let unit_move = UnitMove { player: s.playing, agent, from: *from, to: *to };
+                let idx = s.moves.binary_search(&unit_move).unwrap_or_else(|idx| idx);
+                s.moves.insert(idx, unit_move);
+
+                s.zobrist ^= zobrist::unit_key(*from, Stage::Available, s.playing)
+                    ^ zobrist::unit_key(*to, Stage::Moved, s.playing)
+                    ^ zobrist::move_key(&unit_move);
+
+                Undo::Move(idx)
+            }
+        })
+    }
+
+    /// Takes back the action `undo` was returned for, which has to be the last action applied to `s`
+    pub fn undo(&self, s: &mut MAPFState, undo: Undo) {
+        match undo {
+            Undo::Move(idx) => {
+                let m = s.moves.remove(idx);
+                s.units_available.xor_inline_by_idx(m.from.0, m.from.1, m.player);
+                s.units_moved.xor_inline_by_idx(m.to.0, m.to.1, m.player);
+                s.zobrist ^= zobrist::unit_key(m.from, Stage::Available, m.player)
+                    ^ zobrist::unit_key(m.to, Stage::Moved, m.player)
+                    ^ zobrist::move_key(&m);
+            }
+            Undo::Handover(player, zobrist) => {
+                Self::toggle_shown_moves(s, player);
+                s.playing = player;
+                s.zobrist = zobrist;
+            }
+            Undo::Commit(undo) => {
+                let undo = *undo;
+
+                for saved in undo.cells.iter().rev() {
+                    s.restore_cell(saved);
+                }
+
+                match undo.history {
+                    Some(history) => s.history = history,
+                    None => s.history.pop(),
+                }
+
+                s.moves = undo.moves;
+                s.clashes = undo.clashes;
+                s.playing = undo.playing;
+                s.round = undo.round;
+                s.last_progress = undo.last_progress;
+                s.zobrist = undo.zobrist;
+            }
+        }
+    }
+
+    /// Ends the turn of the playing player. In simultaneous mode the round is handed over to the next player
+    /// without revealing the moves committed so far, once the last player commits the round is resolved.
+    fn commit(&self, s: &mut MAPFState) -> Undo {
+        let upcoming = self.next_player(s.playing, &s.units_begin);
+
+        if self.turn_mode == TurnMode::Simultaneous && self.turn_index(upcoming) > self.turn_index(s.playing) {
+            let undo = Undo::Handover(s.playing, s.zobrist);
+
+            // The moves themselves stay hashed as they are still part of the state
+            Self::toggle_shown_moves(s, s.playing);
+            s.zobrist ^= zobrist::side_to_move_key(s.playing) ^ zobrist::side_to_move_key(upcoming);
+            s.playing = upcoming;
+
+            return undo;
+        }
+
+        let mut cells: Vec<(usize, usize)> = s.moves.iter().flat_map(|m| [m.from, m.to]).collect();
+        cells.sort();
+        cells.dedup();
+
+        let mut saved: Vec<SavedCell> = cells.iter().map(|&cell| s.save_cell(cell)).collect();
+
+        // The key is kept up to date by taking out the cells of the moves here and putting them back once resolved
+        let mut zobrist = s.zobrist ^ zobrist::side_to_move_key(s.playing);
+        for &cell in &cells {
+            zobrist ^= s.cell_zobrist(cell);
+        }
+        for m in &s.moves {
+            zobrist ^= zobrist::move_key(m);
+        }
+
+        let (clashes, destroyed) = self.resolve_round(s);
+
+        for &(a0, a1) in &cells {
+            let unit = s.units_begin.get(a0, a1).unwrap_or(0);
+            s.units_available.insert(a0, a1, unit);
+            s.units_moved.insert(a0, a1, 0);
+        }
+
+        // Units gain their movement points once their player's turn is over
+        let max_points = self.definition.max_points();
+        for (a0, a1, player) in s.units_begin.get_nnz() {
+            if self.turn_mode == TurnMode::Simultaneous || player == s.playing {
+                let points = s.points.get(a0, a1).unwrap_or(0);
+                let gained = points.saturating_add(self.definition.movement_points).min(max_points);
+
+                if cells.binary_search(&(a0, a1)).is_err() {
+                    saved.push(s.save_cell((a0, a1)));
+                    zobrist ^= zobrist::points_key((a0, a1), points) ^ zobrist::points_key((a0, a1), gained);
+                }
+                s.points.insert(a0, a1, gained);
+            }
+        }
+
+        for &cell in &cells {
+            zobrist ^= s.cell_zobrist(cell);
+        }
+
+        let playing = self.next_player(s.playing, &s.units_begin);
+        let round_over = self.turn_mode == TurnMode::Simultaneous
+            || self.turn_index(playing) <= self.turn_index(s.playing);
+
+        let reached_goal = s.moves.iter().any(|m| {
+            m.from != m.to
+                && s.units_begin.get(m.to.0, m.to.1) == Some(m.player)
+                && s.labels.get(m.to.0, m.to.1).unwrap_or(0) == m.agent
+                && self.definition.is_goal_of(m.to, m.player, m.agent)
+        });
+
+        let undo = CommitUndo {
+            cells: saved,
+            moves: std::mem::take(&mut s.moves),
+            clashes: std::mem::replace(&mut s.clashes, clashes),
+            playing: s.playing,
+            round: s.round,
+            last_progress: s.last_progress,
+            zobrist: s.zobrist,
+            // Positions from before a unit was destroyed can not occur again
+            history: destroyed.then(|| std::mem::take(&mut s.history)),
+        };
+
+        if destroyed || reached_goal {
+            s.last_progress = s.round + 1;
+        }
+        if round_over {
+            s.round += 1;
+        }
+        s.playing = playing;
+        s.zobrist = zobrist ^ zobrist::side_to_move_key(playing);
+        s.history.push(s.zobrist);
+
+        Undo::Commit(Box::new(undo))
+    }
+
+    /// Takes the moves of `player` off the board or puts them back, they stay in `MAPFState::moves`
+    fn toggle_shown_moves(s: &mut MAPFState, player: u8) {
+        for m in s.moves.iter().filter(|m| m.player == player) {
+            s.units_available.xor_inline_by_idx(m.from.0, m.from.1, player);
+            s.units_moved.xor_inline_by_idx(m.to.0, m.to.1, player);
+            s.zobrist ^= zobrist::unit_key(m.from, Stage::Available, player) ^ zobrist::unit_key(m.to, Stage::Moved, player);
+        }
+    }
+
+    /// Removes every unit of the playing player from the game and passes the turn on
+    pub fn forfeit(&self, s: &MAPFState) -> MAPFState {
+        let mut state = s.clone();
+
+        state.units_begin.clear_value(s.playing);
+        state.units_available.clear_value(s.playing);
+        state.units_moved.clear_value(s.playing);
+        state.moves.retain(|m| m.player != s.playing);
+
+        for (idx, agent) in s.definition.agents.iter().enumerate() {
+            if agent.player == s.playing {
+                state.labels.clear_value(idx as u16 + 1);
+            }
+        }
+
+        state.zobrist = state.compute_zobrist();
+
+        self.next(&state, &Commit)
+    }
+
+    pub fn with_move_ordering(mut self, move_ordering: MoveOrdering) -> Self {
+        self.move_ordering = move_ordering;
+        self
+    }
+
+    /// First unit of the playing player in reading order that has not moved this turn
+    pub fn next_in_order(&self, s: &MAPFState) -> Option<(usize, usize)> {
+        s.units_available.get_nnz()
+            .into_iter()
+            .find(|&(_, _, player)| player == s.playing)
+            .map(|(a0, a1, _)| (a0, a1))
+    }
+
+    pub fn with_move_set(mut self, move_set: MoveSet) -> Self {
+        Arc::make_mut(&mut self.definition).move_set = move_set;
+        self
+    }
+
+    /// Limits what every player observes to the cells within `vision` of its units, see `observe`
+    pub fn with_vision(mut self, vision: Option<u32>) -> Self {
+        Arc::make_mut(&mut self.definition).vision = vision;
+        self
+    }
+
+    pub fn with_limits(mut self, limits: GameLimits) -> Self {
+        Arc::make_mut(&mut self.definition).limits = limits;
+        self
+    }
+
+    pub fn with_collision_rules(mut self, collision_rules: CollisionRules) -> Self {
+        self.collision_rules = collision_rules;
+        self
+    }
+
+    /// Next player in turn order after `current` that still has units in `positions`
+    pub fn next_player(&self, current: u8, positions: &SparseMatrix2D) -> u8 {
+        let order = &self.definition.players;
+
+        if order.is_empty() {
+            return current;
+        }
+
+        let start = order.iter().position(|&p| p == current).unwrap_or(order.len() - 1);
+
+        for offset in 1..=order.len() {
+            let candidate = order[(start + offset) % order.len()];
+            if positions.count_value(candidate) > 0 {
+                return candidate;
+            }
+        }
+
+        current
+    }
+
+    fn turn_index(&self, player: u8) -> usize {
+        self.definition.players.iter().position(|&p| p == player).unwrap_or(0)
+    }
+
+    /// Applies all moves of the round to the units of `s` and resolves collisions by the collision rules, first
+    /// between units swapping cells and then in every cell a unit moved into. Moved units pay for the cell they
+    /// entered. Only the cells of the moves change. Returns the clashes and whether any unit was destroyed.
+    fn resolve_round(&self, s: &mut MAPFState) -> (Vec<Clash>, bool) {
+        let mut clashes = Vec::new();
+
+        // Waiting units stay put and defend their cell like any unit that did not move
+        let moves: Vec<UnitMove> = s.moves.iter().filter(|m| m.from != m.to).copied().collect();
+        let saved: Vec<u8> = moves.iter().map(|m| s.points.get(m.from.0, m.from.1).unwrap_or(0)).collect();
+
+        for m in &moves {
+            s.units_begin.xor_inline_by_idx(m.from.0, m.from.1, m.player);
+            s.labels.insert(m.from.0, m.from.1, 0);
+            s.points.insert(m.from.0, m.from.1, 0);
+        }
+
+        let mut arrivals: BTreeMap<(usize, usize), Vec<usize>> = BTreeMap::new();
+
+        for (i, m) in moves.iter().enumerate() {
+            let swapped = moves.iter()
+                .enumerate()
+                .find(|&(j, o)| j != i && o.from == m.to && o.to == m.from);
+
+            if let Some((j, o)) = swapped {
+                let winner = self.collision_rules.resolve(0, &[m.player, o.player]);
+
+                if i < j {
+                    clashes.push(Clash::Edge { cells: (m.from, m.to), players: (m.player, o.player), winner });
+                }
+
+                if winner != m.player || o.player == m.player {
+                    continue;
+                }
+            }
+
+            arrivals.entry(m.to).or_default().push(i);
+        }
+
+        // Every moved unit and every defender of a contested cell is involved, one survivor per cell at most
+        let mut involved = moves.len();
+        let mut survivors = 0;
+
+        for ((a0, a1), arrived) in arrivals {
+            let defender = s.units_begin.get(a0, a1).unwrap_or(0);
+            let attackers: Vec<u8> = arrived.iter().map(|&i| moves[i].player).collect();
+            let winner = self.collision_rules.resolve(defender, &attackers);
+
+            if defender != 0 || attackers.len() > 1 {
+                clashes.push(Clash::Vertex { cell: (a0, a1), defender, attackers, winner });
+            }
+
+            involved += (defender != 0) as usize;
+            survivors += (winner != 0) as usize;
+            s.units_begin.insert(a0, a1, winner);
+
+            // A surviving defender keeps its label, otherwise the first arrival of the winner holds the cell
+            if winner != defender {
+                let arrival = arrived.iter().copied().find(|&i| moves[i].player == winner);
+                s.labels.insert(a0, a1, arrival.map_or(0, |i| moves[i].agent));
+                s.points.insert(a0, a1, arrival.map_or(0, |i| saved[i]).saturating_sub(self.definition.cost((a0, a1))));
+            }
+        }
+
+        (clashes, survivors < involved)
+    }
+
     pub fn get_any_goal_state(&self) -> MAPFState{
         MAPFState{
             definition: self.definition.clone(),
@@ -331,6 +1061,14 @@ impl MAPFEnvironment {
             units_available: SparseMatrix2D::new_by_shape(self.definition.shape),
             special_state: AnyGoal,
             playing: 0,
+            moves: Vec::new(),
+            clashes: Vec::new(),
+            round: 0,
+            last_progress: 0,
+            history: History::default(),
+            zobrist: 0,
+            labels: SparseMatrix2D::new_by_shape(self.definition.shape),
+            points: SparseMatrix2D::new_by_shape(self.definition.shape),
             units_moved: SparseMatrix2D::new_by_shape(self.definition.shape)
         }
     }
@@ -393,17 +1131,33 @@ mod tests {
                             shape: (10, 10),
                         }
                     },
-                    goals_num: [0, 3, 3],
+                    goals_num: vec![0, 3, 3],
+                    players: vec![1, 2],
+                    limits: GameLimits::default(),
+                    move_set: MoveSet::default(),
+                    costs: SparseMatrix2D::new(10, 10),
+                    terrain: TERRAIN.to_vec(),
+                    movement_points: 1,
+                    vision: None,
+                    metadata: MapMetadata::default(),
+                    agents: Vec::new(),
                 }),
+                turn_mode: TurnMode::Alternating,
+                move_ordering: MoveOrdering::Free,
+                collision_rules: CollisionRules::default(),
             }
         }
     }
 
+    use crate::ai::greedy::GreedyAI;
     use crate::deps::sparse::SparseMatrix2D;
-    use crate::deps::state_definition::StateEnvironment;
-    use crate::mapf::action::MAPFAction;
-    use crate::mapf::definition::MAPFDefinition;
-    use crate::mapf::environment::MAPFEnvironment;
+    use crate::deps::state_definition::{FallibleStateEnvironment, StateEnvironment, StateStatus};
+    use crate::mapf::action::{JointTurn, MAPFAction, MoveSet, KNIGHT_MOVES};
+    use crate::mapf::collision::{Clash, CollisionRules, CombatRule, FriendlyCollisions};
+    use crate::mapf::definition::{Agent, GameLimits, MAPFDefinition, MapMetadata, Tiebreak, TERRAIN};
+    use crate::mapf::environment::{IllegalAction, MAPFEnvironment, MAPFErrorLoad, MoveOrdering, TurnMode};
+    use crate::mapf::outcome::TerminationReason;
+    use crate::mapf::state::{MAPFState, SpecialState};
     use std::sync::Arc;
 
     #[test]
@@ -433,6 +1187,583 @@ mod tests {
         problem.get_status(&commited);
     }
 
+    #[test]
+    fn test_three_players() {
+        let problem = MAPFEnvironment::new_from_file("./maps/test_three_way.test.txt").unwrap();
+        assert_eq!(problem.definition.players, vec![1, 2, 3]);
+        assert_eq!(problem.definition.goals_num, vec![0, 1, 1, 1]);
+
+        let mut state = problem.get_initial_state();
+        assert_eq!(state.playing, 1);
+
+        state = problem.next(&state, &MAPFAction::Move((0, 0), (0, 1)));
+        state = problem.next(&state, &MAPFAction::Commit);
+        assert_eq!(state.playing, 2);
+
+        state = problem.next(&state, &MAPFAction::Commit);
+        assert_eq!(state.playing, 3);
+
+        state = problem.next(&state, &MAPFAction::Commit);
+        assert_eq!(state.playing, 1);
+        assert_eq!(problem.get_status(&state), StateStatus::Running);
+
+        // Player 1 rams player 2, both are eliminated and player 3 is the last one standing
+        state = problem.next(&state, &MAPFAction::Move((0, 1), (0, 2)));
+        state = problem.next(&state, &MAPFAction::Commit);
+        assert_eq!(state.clashes, vec![Clash::Vertex { cell: (0, 2), defender: 2, attackers: vec![1], winner: 0 }]);
+        assert_eq!(state.playing, 3);
+        assert_eq!(problem.get_status(&state), StateStatus::Winner(3));
+
+        let outcome = problem.get_outcome(&state);
+        assert_eq!(outcome.reason, Some(TerminationReason::Elimination));
+        assert_eq!(outcome.round, 1);
+        assert_eq!(outcome.units, vec![0, 0, 0, 1]);
+    }
+
+    #[test]
+    fn test_three_players_goal() {
+        let problem = MAPFEnvironment::new_from_file("./maps/test_three_way.test.txt").unwrap();
+        let mut state = problem.get_initial_state();
+
+        state = problem.next(&state, &MAPFAction::Commit);
+        state = problem.next(&state, &MAPFAction::Commit);
+        state = problem.next(&state, &MAPFAction::Move((0, 4), (1, 4)));
+        state = problem.next(&state, &MAPFAction::Commit);
+
+        assert_eq!(problem.get_status(&state), StateStatus::Winner(3));
+
+        let outcome = problem.get_outcome(&state);
+        assert_eq!(outcome.reason, Some(TerminationReason::AllGoalsReached));
+        assert_eq!(outcome.goals_held, vec![0, 0, 0, 1]);
+        assert_eq!(outcome.units_of(1), 1);
+    }
+
+    #[test]
+    fn test_simultaneous_same_cell() {
+        let problem = MAPFEnvironment::new_from_file("./maps/test_duel.test.txt")
+            .unwrap()
+            .with_turn_mode(TurnMode::Simultaneous);
+        let mut state = problem.get_initial_state();
+
+        state = problem.next(&state, &MAPFAction::Move((0, 1), (0, 2)));
+        state = problem.next(&state, &MAPFAction::Commit);
+
+        // Player 2 does not see the committed move of player 1
+        assert_eq!(state.playing, 2);
+        assert_eq!(state.units_available.get(0, 1).unwrap(), 1);
+        assert_eq!(state.units_moved.get_nnz_sum(), 0);
+        assert_eq!(problem.get_status(&state), StateStatus::Running);
+
+        state = problem.next(&state, &MAPFAction::Move((0, 3), (0, 2)));
+        state = problem.next(&state, &MAPFAction::Commit);
+
+        assert_eq!(state.units_begin.get_nnz_sum(), 0);
+        assert!(state.moves.is_empty());
+        assert_eq!(problem.get_status(&state), StateStatus::Draw);
+        assert_eq!(problem.get_outcome(&state).reason, Some(TerminationReason::MutualElimination));
+    }
+
+    #[test]
+    fn test_simultaneous_swap() {
+        let problem = MAPFEnvironment::new_from_file("./maps/test_duel.test.txt")
+            .unwrap()
+            .with_turn_mode(TurnMode::Simultaneous);
+        let mut state = problem.get_initial_state();
+
+        state = problem.next(&state, &MAPFAction::Move((0, 1), (0, 2)));
+        state = problem.next(&state, &MAPFAction::Commit);
+        state = problem.next(&state, &MAPFAction::Commit);
+
+        assert_eq!(state.playing, 1);
+        assert_eq!(state.units_begin.get(0, 2).unwrap(), 1);
+        assert_eq!(state.units_begin.get(0, 3).unwrap(), 2);
+
+        state = problem.next(&state, &MAPFAction::Move((0, 2), (0, 3)));
+        state = problem.next(&state, &MAPFAction::Commit);
+        state = problem.next(&state, &MAPFAction::Move((0, 3), (0, 2)));
+        state = problem.next(&state, &MAPFAction::Commit);
+
+        assert_eq!(state.units_begin.get_nnz_sum(), 0);
+        assert_eq!(state.clashes, vec![Clash::Edge { cells: ((0, 2), (0, 3)), players: (1, 2), winner: 0 }]);
+        assert_eq!(problem.get_status(&state), StateStatus::Draw);
+    }
+
+    #[test]
+    fn test_swap_own_units() {
+        let problem = MAPFEnvironment::new();
+        let mut state = problem.get_initial_state();
+
+        state = problem.next(&state, &MAPFAction::Move((3, 0), (4, 0)));
+        state = problem.next(&state, &MAPFAction::Move((4, 0), (3, 0)));
+        state = problem.next(&state, &MAPFAction::Commit);
+
+        assert_eq!(state.units_begin.get(3, 0).unwrap(), 0);
+        assert_eq!(state.units_begin.get(4, 0).unwrap(), 0);
+        assert_eq!(state.units_begin.get(5, 0).unwrap(), 1);
+        assert_eq!(state.clashes, vec![Clash::Edge { cells: ((3, 0), (4, 0)), players: (1, 1), winner: 0 }]);
+    }
+
+    #[test]
+    fn test_validate_action() {
+        let problem = MAPFEnvironment::new();
+        let state = problem.get_initial_state();
+
+        assert_eq!(problem.validate_action(&state, &MAPFAction::Commit), Ok(()));
+        assert_eq!(problem.validate_action(&state, &MAPFAction::Move((3, 0), (2, 0))), Ok(()));
+        assert_eq!(problem.validate_action(&state, &MAPFAction::Move((3, 0), (3, 10))),
+                   Err(IllegalAction::OutOfBounds((3, 10))));
+        assert_eq!(problem.validate_action(&state, &MAPFAction::Move((3, 9), (3, 8))),
+                   Err(IllegalAction::NotYourUnit((3, 9))));
+        assert_eq!(problem.validate_action(&state, &MAPFAction::Move((2, 0), (1, 0))),
+                   Err(IllegalAction::NotYourUnit((2, 0))));
+        assert_eq!(problem.validate_action(&state, &MAPFAction::Move((3, 0), (3, 2))),
+                   Err(IllegalAction::NotAdjacent((3, 0), (3, 2))));
+
+        let moved = problem.next(&state, &MAPFAction::Move((3, 0), (3, 1)));
+        assert_eq!(problem.validate_action(&moved, &MAPFAction::Move((3, 1), (3, 2))),
+                   Err(IllegalAction::UnitAlreadyMoved((3, 1))));
+        assert!(problem.try_next(&moved, &MAPFAction::Move((3, 1), (3, 2))).is_err());
+
+        let mut state = problem.get_initial_state();
+        for a1 in 0..3 {
+            state = problem.next(&state, &MAPFAction::Move((3, a1), (3, a1 + 1)));
+            state = problem.next(&state, &MAPFAction::Commit);
+            state = problem.next(&state, &MAPFAction::Commit);
+        }
+        assert_eq!(problem.validate_action(&state, &MAPFAction::Move((3, 3), (3, 4))),
+                   Err(IllegalAction::Obstacle((3, 4))));
+
+        assert_eq!(problem.validate_action(&problem.get_any_goal_state(), &MAPFAction::Commit),
+                   Err(IllegalAction::SpecialState(SpecialState::AnyGoal)));
+    }
+
+    #[test]
+    fn test_actions_are_legal() {
+        let problem = MAPFEnvironment::new_from_file("./maps/defender.txt").unwrap();
+        let mut state = problem.get_initial_state();
+
+        for _ in 0..4 {
+            let actions = problem.get_actions(&state);
+            for action in actions.iter() {
+                assert_eq!(problem.validate_action(&state, action), Ok(()), "{:?}", action);
+            }
+            state = problem.next(&state, &actions[0]);
+        }
+    }
+
+    #[test]
+    fn test_eight_connected() {
+        let problem = MAPFEnvironment::new().with_move_set(MoveSet::eight_connected(false));
+        let state = problem.get_initial_state();
+
+        assert_eq!(problem.validate_action(&state, &MAPFAction::Move((3, 0), (2, 1))), Ok(()));
+        assert!(problem.get_actions(&state).contains(&MAPFAction::Move((3, 0), (2, 1))));
+
+        // (2, 4) -> (3, 3) passes the obstacle at (3, 4)
+        assert!(!problem.definition.neighbours((2, 4)).contains(&(3, 3)));
+        assert!(!problem.definition.predecessors((3, 3)).contains(&(2, 4)));
+
+        let cutting = MAPFEnvironment::new().with_move_set(MoveSet::eight_connected(true));
+        assert!(cutting.definition.neighbours((2, 4)).contains(&(3, 3)));
+        assert!(cutting.definition.predecessors((3, 3)).contains(&(2, 4)));
+    }
+
+    #[test]
+    fn test_knight_moves() {
+        let problem = MAPFEnvironment::new().with_move_set(MoveSet::custom(KNIGHT_MOVES.to_vec()));
+        let state = problem.get_initial_state();
+
+        let mut neighbours = problem.definition.neighbours((3, 0));
+        neighbours.sort();
+        assert_eq!(neighbours, vec![(1, 1), (2, 2), (4, 2), (5, 1)]);
+        assert_eq!(problem.validate_action(&state, &MAPFAction::Move((3, 0), (3, 1))),
+                   Err(IllegalAction::NotAdjacent((3, 0), (3, 1))));
+    }
+
+    #[test]
+    fn test_waits() {
+        let problem = MAPFEnvironment::new_from_file("./maps/test_duel.test.txt")
+            .unwrap()
+            .with_move_set(MoveSet::four_connected().with_waits(true));
+        let mut state = problem.get_initial_state();
+
+        assert!(problem.get_actions(&state).contains(&MAPFAction::Move((0, 1), (0, 1))));
+
+        state = problem.next(&state, &MAPFAction::Move((0, 1), (0, 1)));
+        state = problem.next(&state, &MAPFAction::Commit);
+        assert_eq!(state.units_begin.get(0, 1).unwrap(), 1);
+
+        // A waiting unit defends its cell like a stationary one
+        state = problem.next(&state, &MAPFAction::Move((0, 3), (0, 2)));
+        state = problem.next(&state, &MAPFAction::Commit);
+        state = problem.next(&state, &MAPFAction::Move((0, 1), (0, 1)));
+        state = problem.next(&state, &MAPFAction::Commit);
+        state = problem.next(&state, &MAPFAction::Move((0, 2), (0, 1)));
+        state = problem.next(&state, &MAPFAction::Commit);
+        assert_eq!(state.clashes, vec![Clash::Vertex { cell: (0, 1), defender: 1, attackers: vec![2], winner: 0 }]);
+    }
+
+    #[test]
+    fn test_move_set_from_header() {
+        let problem = MAPFEnvironment::from_text("moves: 8\nwaits: true\n---\n1.A\n...\nB.2\n").unwrap();
+        assert_eq!(problem.definition.move_set, MoveSet::eight_connected(false).with_waits(true));
+
+        let problem = MAPFEnvironment::from_text("waits: true\nmoves: 0,2 0,-2\n---\n1.A\n...\nB.2\n").unwrap();
+        assert_eq!(problem.definition.move_set, MoveSet::custom(vec![(0, 2), (0, -2)]).with_waits(true));
+        assert_eq!(problem.definition.neighbours((0, 0)), vec![(0, 2), (0, 0)]);
+
+        let result = MAPFEnvironment::from_text("moves: 0;2\n---\n1.A\n");
+        assert!(matches!(result, Err(MAPFErrorLoad::InvalidHeaderValue(_, 1))));
+    }
+
+    #[test]
+    fn test_terrain_costs() {
+        let problem = MAPFEnvironment::new_from_file("./maps/test_swamp.test.txt").unwrap();
+        let mut state = problem.get_initial_state();
+
+        assert_eq!(problem.definition.cost((0, 1)), 3);
+        assert_eq!(problem.definition.cost((1, 1)), 1);
+        assert_eq!(problem.definition.max_points(), 3);
+        assert!(!problem.get_actions(&state).contains(&MAPFAction::Move((0, 0), (0, 1))));
+        assert_eq!(problem.validate_action(&state, &MAPFAction::Move((0, 0), (0, 1))),
+                   Err(IllegalAction::NotEnoughPoints((0, 0), 3, (0, 1))));
+
+        // Saving up for the swamp takes two more turns
+        for _ in 0..2 {
+            state = problem.next(&state, &MAPFAction::Move((1, 0), (1, 1)));
+            state = problem.next(&state, &MAPFAction::Commit);
+            state = problem.next(&state, &MAPFAction::Commit);
+            state = problem.next(&state, &MAPFAction::Move((1, 1), (1, 0)));
+            state = problem.next(&state, &MAPFAction::Commit);
+            state = problem.next(&state, &MAPFAction::Commit);
+        }
+        assert_eq!(state.points.get(0, 0).unwrap(), 3);
+        assert_eq!(state.points.get(1, 0).unwrap(), 1);
+
+        state = problem.try_next(&state, &MAPFAction::Move((0, 0), (0, 1))).unwrap();
+        state = problem.next(&state, &MAPFAction::Commit);
+        assert_eq!(state.units_begin.get(0, 1).unwrap(), 1);
+        assert_eq!(state.points.get(0, 1).unwrap(), 1);
+        assert_eq!(state.points.get(0, 0).unwrap_or(0), 0);
+    }
+
+    #[test]
+    fn test_terrain_from_header() {
+        let problem = MAPFEnvironment::from_text("terrain: %=2 ~=5\nmovement_points: 2\n---\n1%~A\nB..2\n").unwrap();
+
+        assert_eq!(problem.definition.cost((0, 1)), 2);
+        assert_eq!(problem.definition.cost((0, 2)), 5);
+        assert_eq!(problem.definition.movement_points, 2);
+        assert_eq!(problem.to_string(), "1%~A\nB..2\n");
+
+        let state = problem.get_initial_state();
+        assert_eq!(problem.validate_action(&state, &MAPFAction::Move((0, 0), (0, 1))), Ok(()));
+
+        let result = MAPFEnvironment::from_text("---\n1%.A\nB..2\n");
+        assert!(matches!(result, Err(MAPFErrorLoad::InvalidCharacter('%', 2, 2))));
+
+        let result = MAPFEnvironment::from_text("terrain: A=2\n---\n1..A\nB..2\n");
+        assert!(matches!(result, Err(MAPFErrorLoad::InvalidHeaderValue(_, 1))));
+
+        // `%` replaces `,` as the terrain of cost 2, a second symbol of the same cost would not round trip
+        let result = MAPFEnvironment::from_text("terrain: %=2\n---\n1,.A\nB..2\n");
+        assert!(matches!(result, Err(MAPFErrorLoad::InvalidCharacter(',', 3, 2))));
+
+        let result = MAPFEnvironment::from_text("terrain: %=4\nterrain: &=4\n---\n1%&A\nB..2\n");
+        assert!(matches!(result, Err(MAPFErrorLoad::InvalidHeaderValue(_, 2))));
+    }
+
+    #[test]
+    fn test_apply_undo() {
+        let problems = [
+            MAPFEnvironment::new_from_file("./maps/defender.txt").unwrap(),
+            MAPFEnvironment::new_from_file("./maps/test_swamp.test.txt").unwrap(),
+            MAPFEnvironment::new_from_file("./maps/test_two_goals.test.txt").unwrap().labelled().unwrap(),
+            MAPFEnvironment::new_from_file("./maps/test_duel.test.txt").unwrap().with_turn_mode(TurnMode::Simultaneous),
+        ];
+
+        for problem in problems {
+            let mut state = problem.get_initial_state();
+
+            for step in 0..12 {
+                if problem.get_status(&state) != StateStatus::Running {
+                    break;
+                }
+
+                let actions = problem.get_actions(&state);
+                for action in actions.iter() {
+                    let mut applied = state.clone();
+                    let undo = problem.apply(&mut applied, action).unwrap();
+                    assert_eq!(applied, problem.next(&state, action), "{:?}", action);
+                    assert_eq!(applied.zobrist, applied.compute_zobrist(), "{:?}", action);
+
+                    problem.undo(&mut applied, undo);
+                    assert_eq!(applied, state, "{:?}", action);
+                    assert_eq!((applied.round, applied.last_progress), (state.round, state.last_progress));
+                    assert_eq!(applied.clashes, state.clashes);
+                    assert_eq!((applied.zobrist, &applied.history), (state.zobrist, &state.history));
+                }
+
+                state = problem.next(&state, &actions[step % actions.len()]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_canonical_ordering() {
+        let problem = MAPFEnvironment::new_from_file("./maps/test_two_goals.test.txt")
+            .unwrap()
+            .with_move_ordering(MoveOrdering::Canonical);
+        let mut state = problem.get_initial_state();
+
+        assert_eq!(problem.get_actions(&state).as_ref(), &vec![
+            MAPFAction::Move((0, 0), (0, 1)),
+            MAPFAction::Move((0, 0), (1, 0)),
+            MAPFAction::Move((0, 0), (0, 0)),
+        ]);
+        assert_eq!(problem.validate_action(&state, &MAPFAction::Move((1, 0), (1, 1))),
+                   Err(IllegalAction::OutOfOrder((0, 0))));
+        assert_eq!(problem.validate_action(&state, &MAPFAction::Commit), Err(IllegalAction::OutOfOrder((0, 0))));
+
+        state = problem.try_next(&state, &MAPFAction::Move((0, 0), (0, 0))).unwrap();
+        state = problem.try_next(&state, &MAPFAction::Move((1, 0), (1, 1))).unwrap();
+        assert_eq!(problem.get_actions(&state).as_ref(), &vec![MAPFAction::Commit]);
+
+        state = problem.try_next(&state, &MAPFAction::Commit).unwrap();
+        assert_eq!(state.units_begin.get(0, 0).unwrap(), 1);
+        assert_eq!(state.units_begin.get(1, 1).unwrap(), 1);
+        assert!(state.clashes.is_empty());
+    }
+
+    #[test]
+    fn test_canonical_ordering_is_a_product() {
+        fn count_turns(problem: &MAPFEnvironment, s: &MAPFState) -> usize {
+            problem.get_actions(s).iter()
+                .map(|action| match action {
+                    MAPFAction::Commit => 1,
+                    _ => count_turns(problem, &problem.next(s, action)),
+                })
+                .sum()
+        }
+
+        let free = MAPFEnvironment::new_from_file("./maps/test_two_goals.test.txt").unwrap();
+        let canonical = MAPFEnvironment::new_from_file("./maps/test_two_goals.test.txt")
+            .unwrap()
+            .with_move_ordering(MoveOrdering::Canonical);
+
+        // (0, 0) can skip, go right or down and (1, 0) can skip, go up, right or down
+        assert_eq!(count_turns(&canonical, &canonical.get_initial_state()), 3 * 4);
+        assert!(count_turns(&free, &free.get_initial_state()) > 3 * 4);
+        assert_eq!(canonical.joint_turns(&canonical.get_initial_state()).len(),
+                   free.joint_turns(&free.get_initial_state()).len());
+    }
+
+    #[test]
+    fn test_special_state_errors() {
+        let problem = MAPFEnvironment::new_from_file("./maps/test_three_way.test.txt").unwrap();
+        let any_goal = problem.get_any_goal_state();
+        let error = Err(IllegalAction::SpecialState(SpecialState::AnyGoal));
+
+        assert_eq!(problem.try_get_actions(&any_goal).map(|_| ()), error);
+        assert_eq!(problem.try_next(&any_goal, &MAPFAction::Commit).map(|_| ()), error);
+        assert_eq!(problem.try_get_status(&any_goal).map(|_| ()), error);
+        assert_eq!(problem.try_get_outcome(&any_goal).map(|_| ()), error);
+        assert_eq!(problem.validate_action(&any_goal, &MAPFAction::Commit), error);
+        assert!(problem.joint_turns(&any_goal).is_empty());
+        assert_eq!(problem.apply_joint_turn(&any_goal, &JointTurn { actions: Vec::new() }).map(|_| ()), error);
+
+        let mut applied = any_goal.clone();
+        for action in [MAPFAction::Commit, MAPFAction::Move((0, 0), (0, 1))] {
+            assert_eq!(problem.apply(&mut applied, &action).map(|_| ()), error);
+        }
+        assert_eq!(applied, any_goal);
+        assert_eq!(GreedyAI::new().choose(&any_goal, &problem), MAPFAction::Commit);
+
+        let mut state = problem.get_initial_state();
+        assert_eq!(problem.try_get_status(&state), Ok(StateStatus::Running));
+        assert!(!problem.is_goal(&state, &any_goal));
+
+        state = problem.next(&state, &MAPFAction::Commit);
+        state = problem.next(&state, &MAPFAction::Commit);
+        state = problem.next(&state, &MAPFAction::Move((0, 4), (1, 4)));
+        state = problem.next(&state, &MAPFAction::Commit);
+        assert!(problem.is_goal(&state, &any_goal));
+        assert!(problem.is_goal(&any_goal, &any_goal));
+    }
+
+    #[test]
+    fn test_forfeit() {
+        let problem = MAPFEnvironment::new();
+        let mut state = problem.get_initial_state();
+
+        state = problem.next(&state, &MAPFAction::Move((3, 0), (2, 0)));
+        state = problem.forfeit(&state);
+
+        assert_eq!(state.units_begin.count_value(1), 0);
+        assert_eq!(state.playing, 2);
+        assert_eq!(problem.get_status(&state), StateStatus::Winner(2));
+    }
+
+    #[test]
+    fn test_turn_limit() {
+        let problem = MAPFEnvironment::new()
+            .with_limits(GameLimits { turn_limit: Some(2), ..Default::default() });
+        let mut state = problem.get_initial_state();
+
+        for _ in 0..3 {
+            state = problem.next(&state, &MAPFAction::Commit);
+            assert_eq!(problem.get_status(&state), StateStatus::Running);
+        }
+
+        state = problem.next(&state, &MAPFAction::Commit);
+        assert_eq!(state.round, 2);
+        assert_eq!(problem.get_status(&state), StateStatus::Draw);
+        assert_eq!(problem.get_outcome(&state).reason, Some(TerminationReason::TurnLimit));
+    }
+
+    #[test]
+    fn test_no_progress_limit() {
+        let problem = MAPFEnvironment::new_from_file("./maps/test_two_goals.test.txt")
+            .unwrap()
+            .with_limits(GameLimits { no_progress_limit: Some(2), ..Default::default() });
+        let mut state = problem.get_initial_state();
+
+        state = problem.next(&state, &MAPFAction::Commit);
+        state = problem.next(&state, &MAPFAction::Commit);
+
+        // Reaching an own goal counts as progress
+        state = problem.next(&state, &MAPFAction::Move((0, 0), (0, 1)));
+        state = problem.next(&state, &MAPFAction::Commit);
+        state = problem.next(&state, &MAPFAction::Commit);
+        assert_eq!(state.idle_rounds(), 0);
+
+        state = problem.next(&state, &MAPFAction::Commit);
+        state = problem.next(&state, &MAPFAction::Commit);
+        assert_eq!(problem.get_status(&state), StateStatus::Running);
+
+        state = problem.next(&state, &MAPFAction::Commit);
+        state = problem.next(&state, &MAPFAction::Commit);
+        assert_eq!(state.idle_rounds(), 2);
+        assert_eq!(problem.get_status(&state), StateStatus::Draw);
+        assert_eq!(problem.get_outcome(&state).reason, Some(TerminationReason::NoProgress));
+    }
+
+    #[test]
+    fn test_repetition_limit() {
+        let problem = MAPFEnvironment::new()
+            .with_limits(GameLimits { repetition_limit: Some(3), ..Default::default() });
+        let mut state = problem.get_initial_state();
+
+        state = problem.next(&state, &MAPFAction::Move((3, 0), (2, 0)));
+        state = problem.next(&state, &MAPFAction::Commit);
+        state = problem.next(&state, &MAPFAction::Commit);
+        state = problem.next(&state, &MAPFAction::Move((2, 0), (3, 0)));
+        state = problem.next(&state, &MAPFAction::Commit);
+        state = problem.next(&state, &MAPFAction::Commit);
+        assert_eq!(state.repetitions(), 2);
+        assert_eq!(problem.get_status(&state), StateStatus::Running);
+
+        state = problem.next(&state, &MAPFAction::Commit);
+        state = problem.next(&state, &MAPFAction::Commit);
+        assert_eq!(state.repetitions(), 3);
+        assert_eq!(problem.get_outcome(&state).reason, Some(TerminationReason::Repetition));
+    }
+
+    #[test]
+    fn test_limits_from_header() {
+        let grid = std::fs::read_to_string("./maps/test_two_goals.test.txt").unwrap();
+        let problem = MAPFEnvironment::from_text(&format!("turn_limit: 1\ntiebreak: goals_held\n---\n{}", grid)).unwrap();
+
+        assert_eq!(problem.definition.limits, GameLimits {
+            turn_limit: Some(1),
+            tiebreak: Tiebreak::GoalsHeld,
+            ..Default::default()
+        });
+
+        let mut state = problem.get_initial_state();
+        state = problem.next(&state, &MAPFAction::Move((0, 0), (0, 1)));
+        state = problem.next(&state, &MAPFAction::Commit);
+        assert_eq!(problem.get_status(&state), StateStatus::Running);
+        state = problem.next(&state, &MAPFAction::Commit);
+        assert_eq!(problem.get_status(&state), StateStatus::Winner(1));
+
+        let outcome = problem.get_outcome(&state);
+        assert_eq!(outcome.reason, Some(TerminationReason::TurnLimit));
+        assert_eq!(outcome.goals_held_by(1), 1);
+        assert_eq!(outcome.goals_held_by(2), 0);
+
+        let result = MAPFEnvironment::from_text("turns: 1\n---\n1.A\n");
+        assert!(matches!(result, Err(MAPFErrorLoad::UnknownHeaderKey(_, 1))));
+
+        let result = MAPFEnvironment::from_text("turn_limit: many\n---\n1.A\n");
+        assert!(matches!(result, Err(MAPFErrorLoad::InvalidHeaderValue(_, 1))));
+    }
+
+    #[test]
+    fn test_labelled_reading_order() {
+        let problem = MAPFEnvironment::new_from_file("./maps/test_two_goals.test.txt").unwrap().labelled().unwrap();
+
+        assert_eq!(problem.definition.agents, vec![
+            Agent { player: 1, start: (0, 0), goal: (0, 1) },
+            Agent { player: 1, start: (1, 0), goal: (1, 2) },
+            Agent { player: 2, start: (2, 2), goal: (3, 0) },
+        ]);
+
+        let state = problem.get_initial_state();
+        assert_eq!(state.labels.get_nnz(), vec![(0, 0, 1), (1, 0, 2), (2, 2, 3)]);
+
+        assert!(matches!(MAPFEnvironment::new_from_file("./maps/defender.txt").unwrap().labelled(),
+                         Err(MAPFErrorLoad::UnmatchedGoals(_))));
+    }
+
+    #[test]
+    fn test_labelled_goals() {
+        let agents = vec![
+            Agent { player: 1, start: (0, 0), goal: (1, 2) },
+            Agent { player: 1, start: (1, 0), goal: (0, 1) },
+            Agent { player: 2, start: (2, 2), goal: (3, 0) },
+        ];
+        let anonymous = MAPFEnvironment::new_from_file("./maps/test_two_goals.test.txt").unwrap();
+        let labelled = MAPFEnvironment::new_from_file("./maps/test_two_goals.test.txt").unwrap().with_agents(agents).unwrap();
+
+        for problem in [&anonymous, &labelled] {
+            let mut state = problem.get_initial_state();
+            state = problem.next(&state, &MAPFAction::Move((0, 0), (0, 1)));
+            state = problem.next(&state, &MAPFAction::Commit);
+
+            if problem.definition.is_labelled() {
+                // Agent 1 stands on the goal of agent 2, which does not count
+                assert_eq!(state.labels.get(0, 1).unwrap(), 1);
+                assert_eq!(problem.get_outcome(&state).goals_held_by(1), 0);
+            } else {
+                assert_eq!(problem.get_outcome(&state).goals_held_by(1), 1);
+            }
+        }
+
+        let invalid = vec![Agent { player: 1, start: (0, 0), goal: (9, 9) }];
+        assert!(matches!(anonymous.with_agents(invalid), Err(MAPFErrorLoad::InvalidAgent(1))));
+    }
+
+    #[test]
+    fn test_labels_through_collisions() {
+        let problem = MAPFEnvironment::new_from_file("./maps/test_duel.test.txt")
+            .unwrap()
+            .labelled()
+            .unwrap()
+            .with_collision_rules(CollisionRules::new(CombatRule::AttackerWins, FriendlyCollisions::Destroy));
+        let mut state = problem.get_initial_state();
+
+        state = problem.next(&state, &MAPFAction::Move((0, 1), (0, 2)));
+        assert_eq!(state.moves[0].agent, 1);
+        state = problem.next(&state, &MAPFAction::Commit);
+        state = problem.next(&state, &MAPFAction::Commit);
+        assert_eq!(state.labels.get_nnz(), vec![(0, 2, 1), (0, 3, 2)]);
+
+        state = problem.next(&state, &MAPFAction::Move((0, 2), (0, 3)));
+        state = problem.next(&state, &MAPFAction::Commit);
+        assert_eq!(state.labels.get_nnz(), vec![(0, 3, 1)]);
+    }
+
     #[test]
     fn test_load_maps() {
         use std::fs;
@@ -487,4 +1818,20 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_map_comments_and_description() {
+        let text = "; Player 2 can only win by elimination\nname: Wall\ndescription: Get past the wall\n; between the lines\n\
+                    description: into the goals\n---\n; the grid\n1.#A ; the wall\n1.#2\n";
+        let env = MAPFEnvironment::from_text(text).unwrap();
+
+        assert_eq!(env.definition.metadata.name.as_deref(), Some("Wall"));
+        assert_eq!(env.definition.metadata.description.as_deref(), Some("Get past the wall\ninto the goals"));
+        assert_eq!(env.to_string(), "1.#A\n1.#2\n");
+
+        // Comments are dropped when saving, the description is written back one line per key
+        let saved = env.to_text_string();
+        assert_eq!(saved, "name: Wall\ndescription: Get past the wall\ndescription: into the goals\n---\n1.#A\n1.#2\n");
+        assert_eq!(MAPFEnvironment::from_text(&saved).unwrap().settings(), env.settings());
+    }
 }
@@ -0,0 +1,305 @@
+use crate::mapf::definition::MAPFDefinition;
+use crate::mapf::environment::MAPFEnvironment;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use thiserror::Error;
+
+/// How the obstacles of a generated map are laid out
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Layout {
+    /// Every cell is an obstacle with probability `density`
+    Random { density: f64 },
+    /// A perfect maze with corridors on the odd rows and columns
+    Maze,
+    /// Rectangular rooms joined by corridors, the rest of the map is solid
+    Rooms { rooms: usize },
+    /// Random obstacles on the left half mirrored onto the right half, player 2 is the mirror image of player 1
+    Arena { density: f64 },
+}
+
+pub struct GeneratorParams {
+    /// Rows and columns of the map
+    pub shape: (usize, usize),
+    pub layout: Layout,
+    /// Units, and as many goals, of each of the two players
+    pub units: usize,
+    pub seed: u64,
+    /// Whether the units of both players must have the same distances to their closest goals
+    pub fair: bool,
+    /// Maps generated before giving up on finding one that passes the checks
+    pub attempts: u32,
+}
+
+impl Default for GeneratorParams {
+    fn default() -> Self {
+        GeneratorParams {
+            shape: (16, 16),
+            layout: Layout::Random { density: 0.2 },
+            units: 2,
+            seed: 0,
+            fair: false,
+            attempts: 100,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum GeneratorError {
+    #[error("Invalid parameters: {0}")]
+    InvalidParams(&'static str),
+
+    #[error("No generated map passed the checks in {0} attempts")]
+    NoValidMap(u32),
+}
+
+/// Generates a two player map from `params.seed`, the same parameters always give the same map. Player 1
+/// starts in the left half with its goals in the right half and player 2 the other way around.
+pub fn generate(params: &GeneratorParams) -> Result<MAPFEnvironment, GeneratorError> {
+    let (height, width) = params.shape;
+
+    if height < 2 || width < 2 {
+        return Err(GeneratorError::InvalidParams("the map needs at least two rows and columns"));
+    }
+    if params.units == 0 {
+        return Err(GeneratorError::InvalidParams("every player needs a unit"));
+    }
+    if let Layout::Random { density } | Layout::Arena { density } = params.layout {
+        if !(0.0..1.0).contains(&density) {
+            return Err(GeneratorError::InvalidParams("the density must be in [0, 1)"));
+        }
+    }
+
+    let mut rng = StdRng::seed_from_u64(params.seed);
+
+    for _ in 0..params.attempts {
+        let mut grid = match params.layout {
+            Layout::Random { density } => random_grid(params.shape, density, &mut rng),
+            Layout::Maze => maze_grid(params.shape, &mut rng),
+            Layout::Rooms { rooms } => rooms_grid(params.shape, rooms, &mut rng),
+            Layout::Arena { density } => arena_grid(params.shape, density, &mut rng),
+        };
+
+        if !place_units(&mut grid, params.units, matches!(params.layout, Layout::Arena { .. }), &mut rng) {
+            continue;
+        }
+
+        let rows: Vec<String> = grid.iter().map(|row| row.iter().collect()).collect();
+        let rows: Vec<(usize, usize, &str)> = rows.iter()
+            .enumerate()
+            .map(|(idx, row)| (idx + 1, 1, row.as_str()))
+            .collect();
+        let env = MAPFEnvironment::from_grid(&rows, &[]).expect("generated grids only contain valid cells");
+
+        if is_reachable(&env.definition) && (!params.fair || is_fair(&env.definition)) {
+            return Ok(env);
+        }
+    }
+
+    Err(GeneratorError::NoValidMap(params.attempts))
+}
+
+/// `count` maps generated from consecutive seeds starting at `params.seed`
+pub fn generate_suite(params: GeneratorParams, count: u64) -> Vec<Result<MAPFEnvironment, GeneratorError>> {
+    let mut params = params;
+
+    (0..count)
+        .map(|_| {
+            let env = generate(&params);
+            params.seed += 1;
+            env
+        })
+        .collect()
+}
+
+/// Whether every unit can reach at least one goal of its player, ignoring all other units
+pub fn is_reachable(definition: &MAPFDefinition) -> bool {
+    definition.goal_distances().iter().all(|distances| distances.iter().all(Option::is_some))
+}
+
+/// Whether every player has the same multiset of distances from its units to their closest own goals
+pub fn is_fair(definition: &MAPFDefinition) -> bool {
+    let mut per_player = definition.goal_distances();
+    per_player.retain(|distances| !distances.is_empty());
+    per_player.iter_mut().for_each(|distances| distances.sort());
+
+    per_player.windows(2).all(|pair| pair[0] == pair[1])
+}
+
+fn random_grid(shape: (usize, usize), density: f64, rng: &mut StdRng) -> Vec<Vec<char>> {
+    (0..shape.0)
+        .map(|_| (0..shape.1).map(|_| if rng.random_bool(density) { '#' } else { '.' }).collect())
+        .collect()
+}
+
+fn arena_grid(shape: (usize, usize), density: f64, rng: &mut StdRng) -> Vec<Vec<char>> {
+    let mut grid = random_grid(shape, density, rng);
+
+    for row in grid.iter_mut() {
+        for a1 in 0..shape.1 / 2 {
+            row[shape.1 - 1 - a1] = row[a1];
+        }
+    }
+
+    grid
+}
+
+/// Randomized depth first search over the cells with odd coordinates, knocking down the wall between a cell
+/// and every newly visited neighbour
+fn maze_grid(shape: (usize, usize), rng: &mut StdRng) -> Vec<Vec<char>> {
+    let mut grid = vec![vec!['#'; shape.1]; shape.0];
+    let mut stack = vec![(1.min(shape.0 - 1), 1.min(shape.1 - 1))];
+    grid[stack[0].0][stack[0].1] = '.';
+
+    while let Some(&(a0, a1)) = stack.last() {
+        let mut next: Vec<(usize, usize)> = [(0, 2), (2, 0), (0, -2), (-2, 0)].iter()
+            .map(|&(d0, d1): &(isize, isize)| (a0 as isize + d0, a1 as isize + d1))
+            .filter(|&(n0, n1)| n0 > 0 && n1 > 0 && n0 < shape.0 as isize && n1 < shape.1 as isize)
+            .map(|(n0, n1)| (n0 as usize, n1 as usize))
+            .filter(|&(n0, n1)| grid[n0][n1] == '#')
+            .collect();
+
+        if next.is_empty() {
+            stack.pop();
+            continue;
+        }
+
+        next.shuffle(rng);
+        let (n0, n1) = next[0];
+        grid[(a0 + n0) / 2][(a1 + n1) / 2] = '.';
+        grid[n0][n1] = '.';
+        stack.push((n0, n1));
+    }
+
+    grid
+}
+
+/// Rooms of 3 to 6 cells a side, each joined to the previous one by an L shaped corridor between their centres
+fn rooms_grid(shape: (usize, usize), rooms: usize, rng: &mut StdRng) -> Vec<Vec<char>> {
+    let mut grid = vec![vec!['#'; shape.1]; shape.0];
+    let mut previous: Option<(usize, usize)> = None;
+
+    for _ in 0..rooms.max(1) {
+        let size = (rng.random_range(3..=6).min(shape.0), rng.random_range(3..=6).min(shape.1));
+        let corner = (rng.random_range(0..=shape.0 - size.0), rng.random_range(0..=shape.1 - size.1));
+
+        for row in grid.iter_mut().skip(corner.0).take(size.0) {
+            row[corner.1..corner.1 + size.1].fill('.');
+        }
+
+        let centre = (corner.0 + size.0 / 2, corner.1 + size.1 / 2);
+        if let Some(from) = previous {
+            grid[from.0][from.1.min(centre.1)..=from.1.max(centre.1)].fill('.');
+            for row in grid.iter_mut().take(from.0.max(centre.0) + 1).skip(from.0.min(centre.0)) {
+                row[centre.1] = '.';
+            }
+        }
+        previous = Some(centre);
+    }
+
+    grid
+}
+
+/// Places the starts and goals of both players on free cells, mirroring player 1 for player 2 when
+/// `mirrored`. Fails when a half of the map has too few free cells.
+fn place_units(grid: &mut [Vec<char>], units: usize, mirrored: bool, rng: &mut StdRng) -> bool {
+    let width = grid[0].len();
+    let mirror = |(a0, a1): (usize, usize)| (a0, width - 1 - a1);
+
+    let free_cells = |grid: &[Vec<char>], left: bool| -> Vec<(usize, usize)> {
+        grid.iter()
+            .enumerate()
+            .flat_map(|(a0, row)| row.iter().enumerate().map(move |(a1, &ch)| (a0, a1, ch)))
+            .filter(|&(_, a1, ch)| ch == '.' && if left { a1 < width / 2 } else { a1 >= width.div_ceil(2) })
+            .map(|(a0, a1, _)| (a0, a1))
+            .collect()
+    };
+
+    let mut left = free_cells(grid, true);
+    let mut right = free_cells(grid, false);
+    left.shuffle(rng);
+    right.shuffle(rng);
+
+    if mirrored {
+        // The mirror image of a goal of player 1 must not land on a start of player 1
+        let starts: Vec<(usize, usize)> = left.iter().take(units).copied().collect();
+        right.retain(|&cell| !starts.contains(&mirror(cell)));
+    }
+
+    let needed = if mirrored { units } else { 2 * units };
+    if left.len() < needed || right.len() < needed {
+        return false;
+    }
+
+    for idx in 0..units {
+        let (start_1, goal_1) = (left[idx], right[idx]);
+        let (start_2, goal_2) = if mirrored {
+            (mirror(start_1), mirror(goal_1))
+        } else {
+            (right[units + idx], left[units + idx])
+        };
+
+        grid[start_1.0][start_1.1] = '1';
+        grid[goal_1.0][goal_1.1] = 'A';
+        grid[start_2.0][start_2.1] = '2';
+        grid[goal_2.0][goal_2.1] = 'B';
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mapf::generator::{generate, generate_suite, is_fair, is_reachable, GeneratorError, GeneratorParams, Layout};
+    use crate::loops::args;
+
+    #[test]
+    fn test_layouts() {
+        for layout in [
+            Layout::Random { density: 0.25 },
+            Layout::Maze,
+            Layout::Rooms { rooms: 5 },
+            Layout::Arena { density: 0.25 },
+        ] {
+            let params = args!(GeneratorParams, shape: (15, 21), layout: layout, units: 3, seed: 11);
+            let env = generate(&params).unwrap();
+
+            assert_eq!(env.definition.shape, (15, 21));
+            assert_eq!(env.definition.players, vec![1, 2]);
+            assert_eq!(env.definition.goals_num, vec![0, 3, 3], "{:?}", layout);
+            assert_eq!(env.definition.starting_positions.get_nnz().len(), 6);
+            assert!(is_reachable(&env.definition));
+
+            // The same seed gives the same map
+            assert_eq!(generate(&params).unwrap().to_string(), env.to_string());
+        }
+    }
+
+    #[test]
+    fn test_arena_is_mirrored() {
+        let params = args!(GeneratorParams, layout: Layout::Arena { density: 0.3 }, fair: true);
+        let env = generate(&params).unwrap();
+        assert!(is_fair(&env.definition));
+
+        for row in env.to_string().lines() {
+            let mirrored: String = row.chars().rev()
+                .map(|ch| match ch { '1' => '2', '2' => '1', 'A' => 'B', 'B' => 'A', other => other })
+                .collect();
+            assert_eq!(mirrored, row);
+        }
+    }
+
+    #[test]
+    fn test_suite() {
+        let suite = generate_suite(args!(GeneratorParams, shape: (8, 9), layout: Layout::Arena { density: 0.2 }, fair: true), 5);
+
+        assert_eq!(suite.len(), 5);
+        for env in suite {
+            assert!(is_fair(&env.unwrap().definition));
+        }
+
+        let blocked = generate(&args!(GeneratorParams, layout: Layout::Random { density: 0.99 }, attempts: 5));
+        assert!(matches!(blocked, Err(GeneratorError::NoValidMap(5))));
+        assert!(matches!(generate(&args!(GeneratorParams, units: 0)), Err(GeneratorError::InvalidParams(_))));
+    }
+}
@@ -0,0 +1,260 @@
+use crate::mapf::action::MAPFAction::{Commit, Move};
+use crate::mapf::action::{JointTurn, MAPFAction};
+use crate::mapf::environment::{IllegalAction, MAPFEnvironment, MoveOrdering};
+use crate::mapf::state::{MAPFState, SpecialState, Undo};
+use rand::seq::IndexedRandom;
+use rand::Rng;
+use std::collections::HashSet;
+
+impl MAPFEnvironment {
+    /// Every complete turn of the player to move, one per distinct state reached after its commit, none for
+    /// sentinel states. Staying put covers waiting, with canonical move ordering it is played as a skip. The
+    /// number of turns grows exponentially with the number of units.
+    pub fn joint_turns(&self, s: &MAPFState) -> Vec<JointTurn> {
+        if s.special_state != SpecialState::No {
+            return Vec::new();
+        }
+
+        let units = self.units_to_move(s);
+        let mut state = s.clone();
+        let mut seen = HashSet::new();
+        let mut turns = Vec::new();
+
+        self.collect_joint_turns(&mut state, &units, &mut Vec::new(), &mut seen, &mut turns);
+
+        turns
+    }
+
+    /// A random complete turn of the player to move, every unit picks uniformly among staying and its moves
+    pub fn sample_joint_turn<R: Rng>(&self, s: &MAPFState, rng: &mut R) -> JointTurn {
+        let mut state = s.clone();
+        let mut actions = Vec::new();
+
+        for unit in self.units_to_move(s) {
+            let mut options: Vec<Option<MAPFAction>> = self.unit_moves(&state, unit)
+                .into_iter()
+                .filter(|action| self.validate_action(&state, action).is_ok())
+                .map(Some)
+                .collect();
+            options.push(self.skip(unit));
+
+            if let Some(Some(action)) = options.choose(rng) {
+                self.apply(&mut state, action).unwrap();
+                actions.push(action.clone());
+            }
+        }
+
+        JointTurn { actions }
+    }
+
+    /// Plays all moves of `turn` and commits
+    pub fn apply_joint_turn(&self, s: &MAPFState, turn: &JointTurn) -> Result<MAPFState, IllegalAction> {
+        let mut state = s.clone();
+
+        for action in &turn.actions {
+            self.apply(&mut state, action)?;
+        }
+
+        self.apply(&mut state, &Commit)?;
+        Ok(state)
+    }
+
+    fn units_to_move(&self, s: &MAPFState) -> Vec<(usize, usize)> {
+        s.units_available.get_nnz()
+            .into_iter()
+            .filter(|&(_, _, player)| player == s.playing)
+            .map(|(a0, a1, _)| (a0, a1))
+            .collect()
+    }
+
+    /// Action for a unit staying put, only canonical move ordering needs an explicit skip
+    fn skip(&self, unit: (usize, usize)) -> Option<MAPFAction> {
+        (self.move_ordering == MoveOrdering::Canonical).then_some(Move(unit, unit))
+    }
+
+    /// Moves of `unit` away from its cell. Other units of the player are not looked at, whether they are in
+    /// the way depends on the order the moves are played in.
+    fn unit_moves(&self, s: &MAPFState, unit: (usize, usize)) -> Vec<MAPFAction> {
+        let points = s.points.get(unit.0, unit.1).unwrap_or(0);
+
+        self.definition.neighbours(unit)
+            .into_iter()
+            .filter(|&to| to != unit && points >= self.definition.cost(to))
+            .map(|to| Move(unit, to))
+            .collect()
+    }
+
+    /// Plays `actions` in an order in which every one of them is legal, this only matters when friendly
+    /// collisions are illegal: a unit can move into a cell once its own unit has left it. Returns what is
+    /// needed to undo the actions, or `None` with `state` unchanged when there is no such order.
+    fn apply_in_legal_order(&self, state: &mut MAPFState, actions: &[MAPFAction]) -> Option<Vec<(MAPFAction, Undo)>> {
+        let mut remaining: Vec<&MAPFAction> = actions.iter().collect();
+        let mut applied = Vec::new();
+
+        // Moving a unit only ever frees its cell, so taking the first legal action never blocks an order
+        while !remaining.is_empty() {
+            let Some(idx) = remaining.iter().position(|action| self.validate_action(state, action).is_ok()) else {
+                for (_, undo) in applied.into_iter().rev() {
+                    self.undo(state, undo);
+                }
+                return None;
+            };
+
+            let action = remaining.remove(idx).clone();
+            let undo = self.apply(state, &action).unwrap();
+            applied.push((action, undo));
+        }
+
+        Some(applied)
+    }
+
+    fn collect_joint_turns(
+        &self,
+        state: &mut MAPFState,
+        units: &[(usize, usize)],
+        actions: &mut Vec<MAPFAction>,
+        seen: &mut HashSet<MAPFState>,
+        turns: &mut Vec<JointTurn>,
+    ) {
+        let Some((&unit, rest)) = units.split_first() else {
+            let Some(applied) = self.apply_in_legal_order(state, actions) else {
+                return;
+            };
+
+            let undo = self.apply(state, &Commit).unwrap();
+            let fresh = seen.insert(state.clone());
+            self.undo(state, undo);
+
+            if fresh {
+                turns.push(JointTurn { actions: applied.iter().map(|(action, _)| action.clone()).collect() });
+            }
+            for (_, undo) in applied.into_iter().rev() {
+                self.undo(state, undo);
+            }
+            return;
+        };
+
+        let mut options = vec![self.skip(unit)];
+        options.extend(self.unit_moves(state, unit).into_iter().map(Some));
+
+        for action in options {
+            let Some(action) = action else {
+                self.collect_joint_turns(state, rest, actions, seen, turns);
+                continue;
+            };
+
+            actions.push(action);
+            self.collect_joint_turns(state, rest, actions, seen, turns);
+            actions.pop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::deps::state_definition::{FallibleStateEnvironment, StateEnvironment};
+    use crate::mapf::action::MAPFAction;
+    use crate::mapf::collision::{CollisionRules, FriendlyCollisions};
+    use crate::mapf::environment::{MAPFEnvironment, MoveOrdering, TurnMode};
+    use crate::mapf::state::MAPFState;
+    use rand::rng;
+    use std::collections::HashSet;
+
+    /// States reached by every sequence of single actions up to the first commit
+    fn sequential_results(env: &MAPFEnvironment, s: &MAPFState, results: &mut HashSet<MAPFState>) {
+        for action in env.get_actions(s).iter() {
+            match action {
+                MAPFAction::Commit => {
+                    results.insert(env.next(s, action));
+                }
+                // Canonical move ordering needs explicit skips to get to the commit
+                MAPFAction::Move(from, to) if from != to || env.move_ordering == MoveOrdering::Canonical => {
+                    sequential_results(env, &env.next(s, action), results)
+                }
+                MAPFAction::Move(..) => {}
+            }
+        }
+    }
+
+    #[test]
+    fn test_joint_turns_match_sequences() {
+        let problems = [
+            MAPFEnvironment::new_from_file("./maps/test_two_goals.test.txt").unwrap(),
+            MAPFEnvironment::new_from_file("./maps/test_two_goals.test.txt").unwrap().labelled().unwrap(),
+            MAPFEnvironment::new_from_file("./maps/test_swamp.test.txt").unwrap().with_turn_mode(TurnMode::Simultaneous),
+        ];
+
+        for problem in problems {
+            let state = problem.get_initial_state();
+            let turns = problem.joint_turns(&state);
+
+            let mut expected = HashSet::new();
+            sequential_results(&problem, &state, &mut expected);
+
+            let reached: HashSet<MAPFState> = turns.iter()
+                .map(|turn| problem.apply_joint_turn(&state, turn).unwrap())
+                .collect();
+
+            assert_eq!(turns.len(), reached.len());
+            assert_eq!(reached, expected);
+        }
+    }
+
+    #[test]
+    fn test_joint_turns_with_illegal_friendly_collisions() {
+        let rules = CollisionRules { friendly: FriendlyCollisions::Illegal, ..CollisionRules::default() };
+        let problems = [
+            MAPFEnvironment::from_text("11.A\n1..A\nB.22\n").unwrap().with_collision_rules(rules),
+            MAPFEnvironment::from_text("11.A\n1..A\nB.22\n").unwrap()
+                .with_collision_rules(rules)
+                .with_move_ordering(MoveOrdering::Canonical),
+        ];
+
+        for problem in problems {
+            let state = problem.get_initial_state();
+            let turns = problem.joint_turns(&state);
+
+            let mut expected = HashSet::new();
+            sequential_results(&problem, &state, &mut expected);
+
+            let reached: HashSet<MAPFState> = turns.iter()
+                .map(|turn| problem.apply_joint_turn(&state, turn).unwrap())
+                .collect();
+
+            assert_eq!(reached, expected);
+        }
+
+        // The unit at (0, 0) follows the one at (0, 1), which only works when that one moves first
+        let problem = MAPFEnvironment::from_text("11.A\n1..A\nB.22\n").unwrap().with_collision_rules(rules);
+        let chain = [MAPFAction::Move((0, 1), (0, 2)), MAPFAction::Move((0, 0), (0, 1))];
+        assert!(problem.joint_turns(&problem.get_initial_state()).iter().any(|turn| turn.actions == chain));
+    }
+
+    #[test]
+    fn test_joint_turns_deduplicate() {
+        let problem = MAPFEnvironment::new_from_file("./maps/test_two_goals.test.txt").unwrap();
+        let state = problem.get_initial_state();
+        let turns = problem.joint_turns(&state);
+
+        // (0, 0) can stay, go right or down and (1, 0) can stay, go up, right or down. Moving onto the other
+        // unit, either way, and swapping with it all destroy both units.
+        assert_eq!(turns.len(), 3 * 4 - 2);
+        assert!(turns.iter().any(|turn| turn.actions.is_empty()));
+    }
+
+    #[test]
+    fn test_sample_joint_turn() {
+        let problem = MAPFEnvironment::new_from_file("./maps/defender.txt").unwrap();
+        let state = problem.get_initial_state();
+        let mut rng = rng();
+
+        for _ in 0..10 {
+            let turn = problem.sample_joint_turn(&state, &mut rng);
+            let mut next = state.clone();
+            for action in &turn.actions {
+                next = problem.try_next(&next, action).unwrap();
+            }
+            assert_eq!(problem.next(&next, &MAPFAction::Commit), problem.apply_joint_turn(&state, &turn).unwrap());
+        }
+    }
+}
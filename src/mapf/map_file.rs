@@ -0,0 +1,582 @@
+use crate::mapf::action::{MoveSet, KNIGHT_MOVES};
+use crate::mapf::collision::{CombatRule, FriendlyCollisions};
+use crate::mapf::definition::{MAPFDefinition, Tiebreak, TERRAIN};
+use crate::mapf::environment::MAPFErrorLoad::{EmptyGrid, InvalidHeaderValue, Syntax, UnknownHeaderKey};
+use crate::mapf::environment::{MAPFEnvironment, MAPFErrorLoad, MoveOrdering, TurnMode};
+use serde::Deserialize;
+use std::ops::Range;
+use std::{fmt, fs, io, path::Path, sync::Arc};
+use toml::Spanned;
+
+// Names of the rule settings, shared by the parser and the writer
+const TURN_MODES: [(&str, TurnMode); 2] = [
+    ("alternating", TurnMode::Alternating),
+    ("simultaneous", TurnMode::Simultaneous),
+];
+const MOVE_ORDERINGS: [(&str, MoveOrdering); 2] = [
+    ("free", MoveOrdering::Free),
+    ("canonical", MoveOrdering::Canonical),
+];
+const COMBAT_RULES: [(&str, CombatRule); 4] = [
+    ("mutual_destruction", CombatRule::MutualDestruction),
+    ("attacker_wins", CombatRule::AttackerWins),
+    ("defender_wins", CombatRule::DefenderWins),
+    ("numeric_majority", CombatRule::NumericMajority),
+];
+const FRIENDLY_COLLISIONS: [(&str, FriendlyCollisions); 2] = [
+    ("destroy", FriendlyCollisions::Destroy),
+    ("illegal", FriendlyCollisions::Illegal),
+];
+const TIEBREAKS: [(&str, Tiebreak); 2] = [
+    ("draw", Tiebreak::Draw),
+    ("goals_held", Tiebreak::GoalsHeld),
+];
+
+fn lookup<T: Copy>(table: &[(&str, T)], name: &str) -> Option<T> {
+    table.iter().find(|&&(n, _)| n == name).map(|&(_, value)| value)
+}
+
+fn name_of<T: PartialEq>(table: &[(&'static str, T)], value: T) -> &'static str {
+    table.iter().find(|(_, v)| *v == value).map(|&(name, _)| name).unwrap()
+}
+
+/// Value of a map setting, kept typed so the structured format can write it as such
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SettingValue {
+    Text(String),
+    Integer(u64),
+    Flag(bool),
+}
+
+impl fmt::Display for SettingValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SettingValue::Text(text) => write!(f, "{}", text),
+            SettingValue::Integer(value) => write!(f, "{}", value),
+            SettingValue::Flag(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+impl MAPFEnvironment {
+    /// Applies a single `key: value` setting of a map file, `line_number` is only used for errors
+    pub(crate) fn apply_setting(&mut self, key: &str, value: &str, line_number: usize) -> Result<(), MAPFErrorLoad> {
+        let invalid = || InvalidHeaderValue(key.to_string(), line_number);
+
+        match key {
+            "turn_mode" => self.turn_mode = lookup(&TURN_MODES, value).ok_or_else(invalid)?,
+            "move_ordering" => self.move_ordering = lookup(&MOVE_ORDERINGS, value).ok_or_else(invalid)?,
+            "combat" => self.collision_rules.combat = lookup(&COMBAT_RULES, value).ok_or_else(invalid)?,
+            "friendly" => self.collision_rules.friendly = lookup(&FRIENDLY_COLLISIONS, value).ok_or_else(invalid)?,
+            "players" => {
+                // Players are given by the grid, the setting only guards against a truncated or edited grid
+                if value.parse::<usize>().ok() != Some(self.definition.players.len()) {
+                    return Err(invalid());
+                }
+            }
+            _ => Self::apply_definition_setting(Arc::make_mut(&mut self.definition), key, value, line_number)?,
+        }
+
+        Ok(())
+    }
+
+    fn apply_definition_setting(
+        definition: &mut MAPFDefinition,
+        key: &str,
+        value: &str,
+        line_number: usize,
+    ) -> Result<(), MAPFErrorLoad> {
+        let invalid = || InvalidHeaderValue(key.to_string(), line_number);
+        let parse_limit = |value: &str| value.parse::<u32>().map(Some).map_err(|_| invalid());
+        let limits = &mut definition.limits;
+
+        match key {
+            "name" => definition.metadata.name = Some(value.to_string()),
+            "author" => definition.metadata.author = Some(value.to_string()),
+            "description" => {
+                // Repeated keys continue the description on a new line
+                let description = definition.metadata.description.get_or_insert_with(String::new);
+                if !description.is_empty() {
+                    description.push('\n');
+                }
+                description.push_str(value);
+            }
+            "turn_limit" => limits.turn_limit = parse_limit(value)?,
+            "no_progress_limit" => limits.no_progress_limit = parse_limit(value)?,
+            "repetition_limit" => limits.repetition_limit = parse_limit(value)?,
+            "tiebreak" => limits.tiebreak = lookup(&TIEBREAKS, value).ok_or_else(invalid)?,
+            "moves" => {
+                let waits = definition.move_set.waits;
+                definition.move_set = match value {
+                    "4" => MoveSet::four_connected(),
+                    "8" => MoveSet::eight_connected(false),
+                    "8_corner_cutting" => MoveSet::eight_connected(true),
+                    "knight" => MoveSet::custom(KNIGHT_MOVES.to_vec()),
+                    custom => MoveSet::custom(Self::parse_offsets(custom).ok_or_else(invalid)?),
+                }.with_waits(waits);
+            }
+            "waits" => {
+                let waits = value.parse::<bool>().map_err(|_| invalid())?;
+                definition.move_set.waits = waits;
+            }
+            "movement_points" => {
+                definition.movement_points = value.parse::<u8>().ok().filter(|&p| p > 0).ok_or_else(invalid)?;
+            }
+            "vision" => {
+                definition.vision = Some(value.parse::<u32>().map_err(|_| invalid())?);
+            }
+            "terrain" => {
+                // Cells keep only their cost, so every cost needs a single symbol to be written back with. Header
+                // symbols replace the default one of their cost, but two of them can not share a cost.
+                for (symbol, cost) in Self::parse_terrain(value).ok_or_else(invalid)? {
+                    let taken = definition.terrain.iter()
+                        .any(|&(s, c)| s != symbol && c == cost && !TERRAIN.contains(&(s, c)));
+                    if taken {
+                        return Err(invalid());
+                    }
+
+                    definition.terrain.retain(|&(s, c)| s != symbol && c != cost);
+                    definition.terrain.push((symbol, cost));
+                }
+            }
+            "covered_terrain" => {
+                for ((a0, a1), cost) in Self::parse_covered_terrain(value).ok_or_else(invalid)? {
+                    let inside = a0 < definition.shape.0 && a1 < definition.shape.1;
+                    if !inside || definition.obstacles.get(a0, a1).unwrap_or(0) != 0 {
+                        return Err(invalid());
+                    }
+                    definition.costs.insert(a0, a1, cost);
+                }
+            }
+            _ => return Err(UnknownHeaderKey(key.to_string(), line_number)),
+        }
+
+        Ok(())
+    }
+
+    /// Parses the costs of cells written as `a0,a1=cost` separated by whitespace, e.g. `0,1=3 4,2=2`
+    fn parse_covered_terrain(value: &str) -> Option<Vec<((usize, usize), u8)>> {
+        value.split_whitespace()
+            .map(|entry| {
+                let (cell, cost) = entry.split_once('=')?;
+                let (a0, a1) = cell.split_once(',')?;
+                let cost = cost.parse::<u8>().ok().filter(|&c| c > 0)?;
+                Some(((a0.parse().ok()?, a1.parse().ok()?), cost))
+            })
+            .collect()
+    }
+
+    /// Parses terrain written as `symbol=cost` pairs separated by whitespace, e.g. `~=4 %=2`. Symbols used by
+    /// the grid itself can not be redefined.
+    fn parse_terrain(value: &str) -> Option<Vec<(char, u8)>> {
+        value.split_whitespace()
+            .map(|pair| {
+                let (symbol, cost) = pair.split_once('=')?;
+                let mut chars = symbol.chars();
+                let symbol = chars.next().filter(|&c| !matches!(c, '.' | '#' | ';' | '0'..='9' | 'A'..='Z'))?;
+                let cost = cost.parse::<u8>().ok().filter(|&c| c > 0)?;
+                chars.next().is_none().then_some((symbol, cost))
+            })
+            .collect()
+    }
+
+    /// Parses offsets written as `d0,d1` pairs separated by whitespace, e.g. `0,1 1,0 0,-1 -1,0`
+    fn parse_offsets(value: &str) -> Option<Vec<(isize, isize)>> {
+        value.split_whitespace()
+            .map(|pair| {
+                let (d0, d1) = pair.split_once(',')?;
+                Some((d0.trim().parse().ok()?, d1.trim().parse().ok()?))
+            })
+            .collect()
+    }
+
+    /// Every setting that differs from the defaults, in the order they are written to map files
+    pub fn settings(&self) -> Vec<(&'static str, SettingValue)> {
+        use SettingValue::{Flag, Integer, Text};
+
+        let definition = &self.definition;
+        let limits = &definition.limits;
+        let mut settings = Vec::new();
+
+        if let Some(name) = &definition.metadata.name {
+            settings.push(("name", Text(name.clone())));
+        }
+        if let Some(author) = &definition.metadata.author {
+            settings.push(("author", Text(author.clone())));
+        }
+        if let Some(description) = &definition.metadata.description {
+            settings.push(("description", Text(description.clone())));
+        }
+        if self.turn_mode != TurnMode::default() {
+            settings.push(("turn_mode", Text(name_of(&TURN_MODES, self.turn_mode).to_string())));
+        }
+        if self.move_ordering != MoveOrdering::default() {
+            settings.push(("move_ordering", Text(name_of(&MOVE_ORDERINGS, self.move_ordering).to_string())));
+        }
+        if self.collision_rules.combat != CombatRule::default() {
+            settings.push(("combat", Text(name_of(&COMBAT_RULES, self.collision_rules.combat).to_string())));
+        }
+        if self.collision_rules.friendly != FriendlyCollisions::default() {
+            settings.push(("friendly", Text(name_of(&FRIENDLY_COLLISIONS, self.collision_rules.friendly).to_string())));
+        }
+        for (key, limit) in [
+            ("turn_limit", limits.turn_limit),
+            ("no_progress_limit", limits.no_progress_limit),
+            ("repetition_limit", limits.repetition_limit),
+        ] {
+            if let Some(limit) = limit {
+                settings.push((key, Integer(limit as u64)));
+            }
+        }
+        if limits.tiebreak != Tiebreak::default() {
+            settings.push(("tiebreak", Text(name_of(&TIEBREAKS, limits.tiebreak).to_string())));
+        }
+        if let Some(moves) = Self::move_set_name(&definition.move_set) {
+            settings.push(("moves", Text(moves)));
+        }
+        if definition.move_set.waits {
+            settings.push(("waits", Flag(true)));
+        }
+        if definition.movement_points != 1 {
+            settings.push(("movement_points", Integer(definition.movement_points as u64)));
+        }
+        if let Some(vision) = definition.vision {
+            settings.push(("vision", Integer(vision as u64)));
+        }
+
+        let terrain: Vec<String> = definition.terrain.iter()
+            .filter(|entry| !TERRAIN.contains(entry))
+            .map(|(symbol, cost)| format!("{}={}", symbol, cost))
+            .collect();
+        if !terrain.is_empty() {
+            settings.push(("terrain", Text(terrain.join(" "))));
+        }
+
+        // The grid shows the unit or goal of a cell instead of its terrain
+        let covered: Vec<String> = definition.costs.get_nnz()
+            .into_iter()
+            .filter(|&(a0, a1, cost)| {
+                cost > 1
+                    && (definition.starting_positions.get(a0, a1).unwrap_or(0) != 0
+                        || definition.goals.get(a0, a1).unwrap_or(0) != 0)
+            })
+            .map(|(a0, a1, cost)| format!("{},{}={}", a0, a1, cost))
+            .collect();
+        if !covered.is_empty() {
+            settings.push(("covered_terrain", Text(covered.join(" "))));
+        }
+
+        settings
+    }
+
+    /// Name of the move set as written in map files, `None` for the default. Custom offsets are always read
+    /// back with corner cutting.
+    fn move_set_name(move_set: &MoveSet) -> Option<String> {
+        let move_set = move_set.clone().with_waits(false);
+
+        if move_set == MoveSet::four_connected() {
+            None
+        } else if move_set == MoveSet::eight_connected(false) {
+            Some("8".to_string())
+        } else if move_set == MoveSet::eight_connected(true) {
+            Some("8_corner_cutting".to_string())
+        } else if move_set == MoveSet::custom(KNIGHT_MOVES.to_vec()) {
+            Some("knight".to_string())
+        } else {
+            let offsets: Vec<String> = move_set.offsets.iter()
+                .map(|(d0, d1)| format!("{},{}", d0, d1))
+                .collect();
+            Some(offsets.join(" "))
+        }
+    }
+
+    /// The map in the text format, the header is left out when every setting has its default value. Comments
+    /// are not kept.
+    pub fn to_text_string(&self) -> String {
+        let settings = self.settings();
+
+        if settings.is_empty() {
+            return self.to_string();
+        }
+
+        let mut text = String::new();
+        for (key, value) in settings {
+            // Multi-line values, only descriptions, are written as one line per repeated key
+            for line in value.to_string().split('\n') {
+                text.push_str(&format!("{}: {}\n", key, line));
+            }
+        }
+        text.push_str("---\n");
+        text.push_str(&self.to_string());
+        text
+    }
+
+    /// The map as TOML, with the player count and the grid stored next to the settings
+    pub fn to_toml_string(&self) -> String {
+        let mut settings = self.settings();
+        let players = SettingValue::Integer(self.definition.players.len() as u64);
+        let position = settings.iter().take_while(|(key, _)| matches!(*key, "name" | "author")).count();
+        settings.insert(position, ("players", players));
+
+        let mut table = toml::Table::new();
+        for (key, value) in settings {
+            let value = match value {
+                SettingValue::Text(text) => toml::Value::String(text),
+                SettingValue::Integer(value) => toml::Value::Integer(value as i64),
+                SettingValue::Flag(value) => toml::Value::Boolean(value),
+            };
+            table.insert(key.to_string(), value);
+        }
+        table.insert("grid".to_string(), toml::Value::String(self.to_string()));
+
+        toml::to_string_pretty(&table).expect("settings are plain values")
+    }
+
+    /// Saves the map, as TOML for `.toml` paths and in the text format otherwise
+    pub fn save_to_file<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let content = match path.as_ref().extension().and_then(|extension| extension.to_str()) {
+            Some("toml") => self.to_toml_string(),
+            _ => self.to_text_string(),
+        };
+
+        fs::write(path, content)
+    }
+
+    /// Parses a map stored as TOML: the settings as top level keys and the grid as a (multi-line) string under
+    /// `grid`. The values are checked by the same rules as in the text format.
+    pub fn from_toml(content: &str) -> Result<Self, MAPFErrorLoad> {
+        let map: TomlMap = toml::from_str(content).map_err(|e| {
+            let (line, column) = e.span().map_or((1, 1), |span| line_and_column(content, span.start));
+            Syntax(e.message().to_string(), line, column)
+        })?;
+
+        let settings: Vec<(usize, &str, String)> = map.settings()
+            .into_iter()
+            .map(|(key, span, value)| (line_and_column(content, span.start).0, key, value))
+            .collect();
+        let settings: Vec<(usize, &str, &str)> = settings.iter()
+            .map(|(line_number, key, value)| (*line_number, *key, value.as_str()))
+            .collect();
+
+        // Rows of a multi-line string start on the line after its opening quotes, those of a basic string,
+        // with `\n` escapes, all share the line of the key
+        let grid = map.grid.as_ref().ok_or(EmptyGrid)?;
+        let (line, column) = line_and_column(content, grid.span().start);
+        let multi_line = content[grid.span()].starts_with("\"\"\"");
+        let grid: Vec<(usize, usize, &str)> = grid.get_ref()
+            .strip_suffix('\n')
+            .unwrap_or(grid.get_ref())
+            .split('\n')
+            .enumerate()
+            .map(|(idx, row)| match multi_line {
+                true => (line + 1 + idx, 1, row),
+                false => (line, column + 1, row),
+            })
+            .collect();
+
+        if grid.iter().all(|(_, _, row)| row.is_empty()) {
+            return Err(EmptyGrid);
+        }
+
+        Self::from_grid(&grid, &settings)
+    }
+}
+
+/// 1-based line and column of the byte at `offset`
+fn line_and_column(content: &str, offset: usize) -> (usize, usize) {
+    let before = &content[..offset];
+    let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+
+    (before.matches('\n').count() + 1, before[line_start..].chars().count() + 1)
+}
+
+/// Keys of a TOML map file, the same as in the header of the text format
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TomlMap {
+    name: Option<Spanned<String>>,
+    author: Option<Spanned<String>>,
+    description: Option<Spanned<String>>,
+    players: Option<Spanned<u32>>,
+    turn_mode: Option<Spanned<String>>,
+    move_ordering: Option<Spanned<String>>,
+    combat: Option<Spanned<String>>,
+    friendly: Option<Spanned<String>>,
+    turn_limit: Option<Spanned<u32>>,
+    no_progress_limit: Option<Spanned<u32>>,
+    repetition_limit: Option<Spanned<u32>>,
+    tiebreak: Option<Spanned<String>>,
+    moves: Option<Spanned<String>>,
+    waits: Option<Spanned<bool>>,
+    movement_points: Option<Spanned<u32>>,
+    vision: Option<Spanned<u32>>,
+    terrain: Option<Spanned<String>>,
+    covered_terrain: Option<Spanned<String>>,
+    grid: Option<Spanned<String>>,
+}
+
+/// A setting of a TOML map with the span of its value
+type TomlSetting = (&'static str, Range<usize>, String);
+
+impl TomlMap {
+    /// The settings present, to be applied like those of the text format
+    fn settings(&self) -> Vec<TomlSetting> {
+        fn entry<T: ToString>(key: &'static str, value: &Option<Spanned<T>>) -> Option<TomlSetting> {
+            value.as_ref().map(|value| (key, value.span(), value.get_ref().to_string()))
+        }
+
+        [
+            entry("name", &self.name),
+            entry("author", &self.author),
+            entry("description", &self.description),
+            entry("players", &self.players),
+            entry("turn_mode", &self.turn_mode),
+            entry("move_ordering", &self.move_ordering),
+            entry("combat", &self.combat),
+            entry("friendly", &self.friendly),
+            entry("turn_limit", &self.turn_limit),
+            entry("no_progress_limit", &self.no_progress_limit),
+            entry("repetition_limit", &self.repetition_limit),
+            entry("tiebreak", &self.tiebreak),
+            entry("moves", &self.moves),
+            entry("waits", &self.waits),
+            entry("movement_points", &self.movement_points),
+            entry("vision", &self.vision),
+            entry("terrain", &self.terrain),
+            entry("covered_terrain", &self.covered_terrain),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mapf::action::MoveSet;
+    use crate::mapf::collision::{CollisionRules, CombatRule, FriendlyCollisions};
+    use crate::mapf::definition::GameLimits;
+    use crate::mapf::environment::{MAPFEnvironment, MAPFErrorLoad, MoveOrdering, TurnMode};
+    use crate::mapf::map_file::SettingValue;
+    use crate::mapf::record::GameRecord;
+    use std::fs;
+    use std::sync::Arc;
+
+    fn customized() -> MAPFEnvironment {
+        let path = std::env::temp_dir().join("mapf_test_customized.txt");
+        fs::write(
+            &path,
+            "; Two swamps in the way\nname: Swamp duel\nauthor: \"Someone\"\ndescription: First line\ndescription: Second; not a comment\n\
+             terrain: ~=4 %=2\nvision: 3\n---\n1%~A ; the swamp\nB..2\n",
+        ).unwrap();
+
+        MAPFEnvironment::new_from_file(&path)
+            .unwrap()
+            .with_turn_mode(TurnMode::Simultaneous)
+            .with_move_ordering(MoveOrdering::Canonical)
+            .with_collision_rules(CollisionRules::new(CombatRule::DefenderWins, FriendlyCollisions::Illegal))
+            .with_move_set(MoveSet::eight_connected(true).with_waits(true))
+            .with_limits(GameLimits { turn_limit: Some(40), ..GameLimits::default() })
+    }
+
+    #[test]
+    fn test_save_round_trip() {
+        let env = customized();
+        assert_eq!(env.definition.metadata.name.as_deref(), Some("Swamp duel"));
+        assert_eq!(env.definition.metadata.description.as_deref(), Some("First line\nSecond; not a comment"));
+        assert_eq!(env.settings().len(), 12);
+
+        for file in ["mapf_test_saved.txt", "mapf_test_saved.toml"] {
+            let path = std::env::temp_dir().join(file);
+            env.save_to_file(&path).unwrap();
+            let loaded = MAPFEnvironment::new_from_file(&path).unwrap();
+
+            assert_eq!(loaded.settings(), env.settings(), "{}", file);
+            assert_eq!(loaded.to_string(), env.to_string());
+            assert_eq!(loaded.definition.costs, env.definition.costs);
+            assert_eq!(loaded.collision_rules, env.collision_rules);
+        }
+    }
+
+    #[test]
+    fn test_covered_terrain_round_trip() {
+        // The unit of player 1 starts in the swamp and the goal of player 2 lies on rough terrain
+        let mut env = MAPFEnvironment::from_text("1~A\nB.2\n").unwrap();
+        let definition = Arc::make_mut(&mut env.definition);
+        definition.costs.insert(0, 0, 3);
+        definition.costs.insert(1, 0, 2);
+
+        assert_eq!(env.settings(), vec![("covered_terrain", SettingValue::Text("0,0=3 1,0=2".to_string()))]);
+
+        for file in ["mapf_test_covered.txt", "mapf_test_covered.toml"] {
+            let path = std::env::temp_dir().join(file);
+            env.save_to_file(&path).unwrap();
+            let loaded = MAPFEnvironment::new_from_file(&path).unwrap();
+
+            assert_eq!(loaded.definition.costs, env.definition.costs, "{}", file);
+            assert_eq!(loaded.to_string(), "1~A\nB.2\n");
+        }
+
+        // Records embed the map in the text format, so a replay moves at the same costs
+        let record = GameRecord::new(&env);
+        assert_eq!(record.environment().unwrap().definition.costs, env.definition.costs);
+
+        let error = MAPFEnvironment::from_text("covered_terrain: 5,0=3\n---\n1.A\nB.2\n").err().unwrap();
+        assert!(matches!(error, MAPFErrorLoad::InvalidHeaderValue(key, 1) if key == "covered_terrain"));
+    }
+
+    #[test]
+    fn test_default_settings_are_not_written() {
+        let env = MAPFEnvironment::new_from_file("./maps/box.txt").unwrap();
+
+        assert!(env.settings().is_empty());
+        assert_eq!(env.to_text_string(), env.to_string());
+        assert!(env.to_toml_string().starts_with("players = 2\ngrid = \"\"\"\n"));
+    }
+
+    #[test]
+    fn test_toml() {
+        let toml = "# A small duel\nname = \"Duel \\\"one\\\"\"\nplayers = 2\nturn_limit = 1_000 # rounds\nwaits = true\n\ngrid = \"\"\"\n1.A\nB.2\n\"\"\"\n";
+        let env = MAPFEnvironment::from_toml(toml).unwrap();
+
+        assert_eq!(env.definition.metadata.name.as_deref(), Some("Duel \"one\""));
+        assert_eq!(env.definition.limits.turn_limit, Some(1000));
+        assert!(env.definition.move_set.waits);
+        assert_eq!(env.to_string(), "1.A\nB.2\n");
+        assert!(matches!(env.settings()[0], ("name", SettingValue::Text(_))));
+    }
+
+    #[test]
+    fn test_load_errors() {
+        let errors = [
+            ("grid = \"\"\"\n1.A\nB?2\n\"\"\"\n", MAPFErrorLoad::InvalidCharacter('?', 3, 2)),
+            ("players = 2\ngrid = \"\"\"\n1.A\nB.2\n.\n\"\"\"\n", MAPFErrorLoad::InconsistentGrid(5)),
+            ("players = 3\ngrid = \"1.A\\nB.2\"\n", MAPFErrorLoad::InvalidHeaderValue("players".to_string(), 1)),
+            ("grid = \"1.A\"\ncombat = \"duel\"\n", MAPFErrorLoad::InvalidHeaderValue("combat".to_string(), 2)),
+            ("name = \"x\"\n", MAPFErrorLoad::EmptyGrid),
+        ];
+
+        for (toml, expected) in errors {
+            let error = MAPFEnvironment::from_toml(toml).err().unwrap();
+            assert_eq!(error.to_string(), expected.to_string(), "{}", toml);
+        }
+
+        // Syntax errors carry the message of the TOML parser, only where they were found is checked
+        let syntax_errors = [
+            ("grid = \"1.A\"\nname \"x\"\n", (2, 6)),
+            ("[map]\n", (1, 2)),
+            ("vision = 3 3\n", (1, 12)),
+            ("vision = \"far\"\n", (1, 10)),
+            ("grid = \"1.A\ngrid = \"B.2\"\n", (1, 12)),
+            ("turn_limit = 0\nturn_limit = 0\n", (2, 1)),
+        ];
+
+        for (toml, position) in syntax_errors {
+            let error = MAPFEnvironment::from_toml(toml).err().unwrap();
+            assert!(matches!(error, MAPFErrorLoad::Syntax(_, line, column) if (line, column) == position), "{}: {:?}", toml, error);
+        }
+
+        let error = MAPFEnvironment::from_text("turn_mode: blind\n---\n1.A\nB.2\n").err().unwrap();
+        assert!(matches!(error, MAPFErrorLoad::InvalidHeaderValue(key, 1) if key == "turn_mode"));
+    }
+}
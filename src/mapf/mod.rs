@@ -1,4 +1,16 @@
 pub mod environment;
 pub mod state;
 pub mod action;
+pub mod collision;
+pub mod outcome;
+pub mod observation;
+pub mod zobrist;
+pub mod joint;
+pub mod map_file;
+pub mod moving_ai;
+pub mod generator;
+pub mod validation;
+pub mod transform;
+pub mod record;
+pub mod notation;
 mod definition;
\ No newline at end of file
@@ -0,0 +1,255 @@
+use crate::mapf::definition::Agent;
+use crate::mapf::environment::MAPFErrorLoad::{EmptyGrid, InconsistentGrid, InvalidCharacter, Syntax};
+use crate::mapf::environment::{MAPFEnvironment, MAPFErrorLoad};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use std::{fs, path::Path};
+
+/// How the agents of a MovingAI scenario are split into the two players
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TeamSplit {
+    /// Agents take turns joining player 1 and player 2, in scenario order
+    Alternating,
+    /// The agents starting in the first half of the map, along its longer side, play for player 1
+    ByRegion,
+    /// A random half of the agents, shuffled with the given seed, plays for player 1
+    Random(u64),
+}
+
+/// Start and goal cell of a scenario agent
+type StartAndGoal = ((usize, usize), (usize, usize));
+
+/// Fields of a line split on tabs, or on whitespace when it has none, with the column every field starts at
+fn fields(line: &str) -> Vec<(usize, &str)> {
+    let tabs = line.contains('\t');
+    let mut fields = Vec::new();
+    let mut start: Option<usize> = None;
+
+    for (idx, ch) in line.char_indices().chain([(line.len(), '\t')]) {
+        let separator = if tabs { ch == '\t' } else { ch.is_whitespace() };
+
+        match (separator, start) {
+            (true, Some(s)) => {
+                fields.push((s + 1, &line[s..idx]));
+                start = None;
+            }
+            (false, None) => start = Some(idx),
+            _ => {}
+        }
+    }
+
+    fields
+}
+
+fn parse_field<T: std::str::FromStr>(field: Option<&(usize, &str)>, line: usize, what: &str) -> Result<T, MAPFErrorLoad> {
+    let &(column, text) = field.ok_or_else(|| Syntax(format!("missing {}", what), line, 1))?;
+    text.parse().map_err(|_| Syntax(format!("invalid {}", what), line, column))
+}
+
+impl MAPFEnvironment {
+    /// Loads a MovingAI benchmark map and the first `agents` agents of one of its scenarios, all of them when
+    /// `None`, split into two players by `split`. Units are labelled with the goals given by the scenario.
+    pub fn from_moving_ai<P: AsRef<Path>, Q: AsRef<Path>>(
+        map: P,
+        scenario: Q,
+        agents: Option<usize>,
+        split: TeamSplit,
+    ) -> Result<Self, MAPFErrorLoad> {
+        let map = fs::read_to_string(map)?;
+        let scenario = fs::read_to_string(scenario)?;
+
+        Self::parse_moving_ai(&map, &scenario, agents, split)
+    }
+
+    pub fn parse_moving_ai(map: &str, scenario: &str, agents: Option<usize>, split: TeamSplit) -> Result<Self, MAPFErrorLoad> {
+        let env = Self::parse_moving_ai_map(map)?;
+        let (height, width) = env.definition.shape;
+
+        let mut lines = scenario.lines().enumerate().map(|(idx, line)| (idx + 1, line));
+        match lines.next() {
+            Some((_, line)) if line.trim().starts_with("version") => {}
+            _ => return Err(Syntax("expected 'version'".to_string(), 1, 1)),
+        }
+
+        let mut cells: Vec<StartAndGoal> = Vec::new();
+
+        for (line_number, line) in lines {
+            if agents.is_some_and(|agents| cells.len() >= agents) {
+                break;
+            }
+
+            let fields = fields(line);
+            if fields.is_empty() {
+                continue;
+            }
+
+            // bucket, map, width, height, start x, start y, goal x, goal y, optimal length
+            let size: (usize, usize) = (
+                parse_field(fields.get(3), line_number, "height")?,
+                parse_field(fields.get(2), line_number, "width")?,
+            );
+            if size != (height, width) {
+                return Err(Syntax(
+                    format!("scenario is for a {}x{} map, the map is {}x{}", size.1, size.0, width, height),
+                    line_number,
+                    fields[2].0,
+                ));
+            }
+
+            let start_x: usize = parse_field(fields.get(4), line_number, "start x")?;
+            let start_y: usize = parse_field(fields.get(5), line_number, "start y")?;
+            let goal_x: usize = parse_field(fields.get(6), line_number, "goal x")?;
+            let goal_y: usize = parse_field(fields.get(7), line_number, "goal y")?;
+
+            cells.push(((start_y, start_x), (goal_y, goal_x)));
+        }
+
+        let players = Self::split_teams(&cells, (height, width), split);
+        let agents = cells.into_iter()
+            .zip(players)
+            .map(|((start, goal), player)| Agent { player, start, goal })
+            .collect();
+
+        env.with_agents(agents)
+    }
+
+    /// The map alone. `.` and `G` are plain cells and `S`, swamp, becomes the `~` terrain. `@`, `O` and `T` are
+    /// obstacles, and so is `W`: water can only be entered from water in MovingAI, which terrain costs can not
+    /// express.
+    fn parse_moving_ai_map(map: &str) -> Result<Self, MAPFErrorLoad> {
+        let mut lines = map.lines().enumerate().map(|(idx, line)| (idx + 1, line));
+        let mut height: Option<usize> = None;
+        let mut width: Option<usize> = None;
+
+        for (line_number, line) in lines.by_ref() {
+            let fields = fields(line);
+
+            match fields.first().map(|&(_, key)| key) {
+                None | Some("type") => {}
+                Some("height") => height = Some(parse_field(fields.get(1), line_number, "height")?),
+                Some("width") => width = Some(parse_field(fields.get(1), line_number, "width")?),
+                Some("map") => break,
+                Some(_) => return Err(Syntax("unknown header line".to_string(), line_number, 1)),
+            }
+        }
+
+        let (Some(height), Some(width)) = (height, width) else {
+            return Err(Syntax("missing height or width".to_string(), 1, 1));
+        };
+
+        let mut grid: Vec<(usize, usize, String)> = Vec::new();
+
+        for (line_number, line) in lines.take(height) {
+            let line = line.trim_end();
+            if line.chars().count() != width {
+                return Err(InconsistentGrid(line_number));
+            }
+
+            let row = line.chars()
+                .enumerate()
+                .map(|(x, ch)| match ch {
+                    '.' | 'G' => Ok('.'),
+                    'S' => Ok('~'),
+                    '@' | 'O' | 'T' | 'W' => Ok('#'),
+                    _ => Err(InvalidCharacter(ch, line_number, x + 1)),
+                })
+                .collect::<Result<String, MAPFErrorLoad>>()?;
+
+            grid.push((line_number, 1, row));
+        }
+
+        if grid.len() != height {
+            return Err(if grid.is_empty() { EmptyGrid } else { InconsistentGrid(grid.last().unwrap().0 + 1) });
+        }
+
+        let grid: Vec<(usize, usize, &str)> = grid.iter()
+            .map(|(line_number, column, row)| (*line_number, *column, row.as_str()))
+            .collect();
+
+        Self::from_grid(&grid, &[])
+    }
+
+    /// Player of every agent under `split`
+    fn split_teams(cells: &[StartAndGoal], shape: (usize, usize), split: TeamSplit) -> Vec<u8> {
+        let mut order: Vec<usize> = (0..cells.len()).collect();
+
+        match split {
+            TeamSplit::Alternating => {}
+            TeamSplit::ByRegion => {
+                let along = |start: (usize, usize)| if shape.1 >= shape.0 { (start.1, start.0) } else { start };
+                order.sort_by_key(|&idx| along(cells[idx].0));
+            }
+            TeamSplit::Random(seed) => order.shuffle(&mut StdRng::seed_from_u64(seed)),
+        }
+
+        let mut players = vec![0; cells.len()];
+        for (rank, idx) in order.into_iter().enumerate() {
+            players[idx] = match split {
+                TeamSplit::Alternating => rank as u8 % 2 + 1,
+                _ => if rank < cells.len().div_ceil(2) { 1 } else { 2 },
+            };
+        }
+
+        players
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::deps::state_definition::StateEnvironment;
+    use crate::mapf::environment::{MAPFEnvironment, MAPFErrorLoad};
+    use crate::mapf::moving_ai::TeamSplit;
+
+    const MAP: &str = "./maps/test-room-8-6.map";
+    const SCENARIO: &str = "./maps/test-room-8-6-even-1.scen";
+
+    fn players(split: TeamSplit) -> Vec<u8> {
+        let env = MAPFEnvironment::from_moving_ai(MAP, SCENARIO, None, split).unwrap();
+        env.definition.agents.iter().map(|agent| agent.player).collect()
+    }
+
+    #[test]
+    fn test_moving_ai_import() {
+        let env = MAPFEnvironment::from_moving_ai(MAP, SCENARIO, Some(2), TeamSplit::Alternating).unwrap();
+
+        assert_eq!(env.definition.shape, (6, 8));
+        assert_eq!(env.to_string().lines().nth(2), Some("..##..#."));
+        assert_eq!(env.definition.agents.len(), 2);
+        assert_eq!(env.definition.agents[1].start, (0, 7));
+        assert_eq!(env.definition.agents[1].goal, (5, 0));
+        assert_eq!(env.definition.players, vec![1, 2]);
+        assert!(!env.get_actions(&env.get_initial_state()).is_empty());
+
+        let map = "type octile\nheight 2\nwidth 3\nmap\n.SW\n...\n";
+        let env = MAPFEnvironment::parse_moving_ai(map, "version 1\n", None, TeamSplit::Alternating).unwrap();
+        assert_eq!(env.to_string(), ".~#\n...\n");
+        assert_eq!(env.definition.cost((0, 1)), 3);
+    }
+
+    #[test]
+    fn test_team_splits() {
+        assert_eq!(players(TeamSplit::Alternating), vec![1, 2, 1, 2]);
+        assert_eq!(players(TeamSplit::ByRegion), vec![1, 2, 2, 1]);
+
+        let random = players(TeamSplit::Random(7));
+        assert_eq!(random, players(TeamSplit::Random(7)));
+        assert_eq!(random.iter().filter(|&&p| p == 1).count(), 2);
+    }
+
+    #[test]
+    fn test_moving_ai_errors() {
+        let map = "type octile\nheight 2\nwidth 3\nmap\n..@\n.X.\n";
+        let error = MAPFEnvironment::parse_moving_ai(map, "version 1\n", None, TeamSplit::Alternating).err().unwrap();
+        assert!(matches!(error, MAPFErrorLoad::InvalidCharacter('X', 6, 2)));
+
+        let map = "type octile\nheight 2\nwidth 3\nmap\n..@\n...\n";
+        let scenario = "version 1\n0\tm.map\t3\t2\t0\t0\t1\t1\t1.4\n0\tm.map\t4\t2\t0\t1\t1\t0\t1.4\n";
+        let error = MAPFEnvironment::parse_moving_ai(map, scenario, None, TeamSplit::Alternating).err().unwrap();
+        assert!(matches!(error, MAPFErrorLoad::Syntax(_, 3, 9)));
+
+        let scenario = "version 1\n0\tm.map\t3\t2\t2\t0\t1\t1\t1.4\n";
+        let error = MAPFEnvironment::parse_moving_ai(map, scenario, None, TeamSplit::Alternating).err().unwrap();
+        assert!(matches!(error, MAPFErrorLoad::InvalidAgent(1)));
+    }
+}
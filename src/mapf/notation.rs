@@ -0,0 +1,328 @@
+use crate::deps::sparse::SparseMatrix2D;
+use crate::deps::state_definition::StateEnvironment;
+use crate::mapf::action::UnitMove;
+use crate::mapf::environment::MAPFEnvironment;
+use crate::mapf::state::{History, MAPFState};
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum NotationError {
+    #[error("{0} at column {1}")]
+    Syntax(String, usize),
+
+    #[error("Notation is for map '{0}'")]
+    WrongMap(String),
+
+    #[error("Board has {0} rows of {1} cells, the map {2} rows of {3}")]
+    WrongShape(usize, usize, usize, usize),
+
+    #[error("Unit at {0:?} stands on an obstacle or belongs to a player not on the map")]
+    InvalidUnit((usize, usize)),
+}
+
+/// Reads a notation one character at a time, tracking the 1-based column for errors
+struct Cursor<'a> {
+    chars: std::iter::Peekable<std::str::CharIndices<'a>>,
+    offset: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().map(|&(_, c)| c)
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        self.chars.next().map(|(idx, c)| {
+            self.offset = idx + c.len_utf8();
+            c
+        })
+    }
+
+    fn column(&mut self) -> usize {
+        self.chars.peek().map_or(self.offset, |&(idx, _)| idx) + 1
+    }
+
+    fn error(&mut self, message: &str) -> NotationError {
+        NotationError::Syntax(message.to_string(), self.column())
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), NotationError> {
+        if self.peek() == Some(expected) {
+            self.bump();
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", expected)))
+        }
+    }
+
+    fn number<T: std::str::FromStr>(&mut self) -> Result<T, NotationError> {
+        let column = self.column();
+        let mut digits = String::new();
+
+        while let Some(c) = self.peek().filter(char::is_ascii_digit) {
+            digits.push(c);
+            self.bump();
+        }
+
+        digits.parse().map_err(|_| NotationError::Syntax("expected a number".to_string(), column))
+    }
+
+    fn digit(&mut self) -> Result<u8, NotationError> {
+        match self.peek().and_then(|c| c.to_digit(10)) {
+            Some(digit) => {
+                self.bump();
+                Ok(digit as u8)
+            }
+            None => Err(self.error("expected a player")),
+        }
+    }
+
+    fn cell(&mut self) -> Result<(usize, usize), NotationError> {
+        let a0 = self.number()?;
+        self.expect(',')?;
+        Ok((a0, self.number()?))
+    }
+}
+
+impl MAPFState {
+    /// Compact notation of the state, read back by `MAPFEnvironment::parse_notation`. Six fields separated by
+    /// spaces, in the spirit of FEN:
+    ///
+    /// 1. the map name with spaces replaced by `_`, or `-` for unnamed maps
+    /// 2. the board, rows separated by `/`. Every cell is `.` when empty, the player's digit for a unit that has
+    ///    not moved yet, or `(bam)` with the players in `units_begin`, `units_available` and `units_moved`. A unit
+    ///    is followed by `@id` in labelled mode and by `+points` when its points differ from the map's default.
+    /// 3. the player to move
+    /// 4. completed rounds
+    /// 5. the round progress was last made in
+    /// 6. the moves made this round as `player:a0,a1>b0,b1` separated by `;`, or `-`
+    ///
+    /// The history of positions is not kept, repetitions are counted from the parsed state on.
+    pub fn to_notation(&self) -> String {
+        let definition = &self.definition;
+        let map = definition.metadata.name.as_deref().map_or("-".to_string(), |name| name.replace(' ', "_"));
+
+        let rows: Vec<String> = (0..definition.shape.0)
+            .map(|a0| (0..definition.shape.1).map(|a1| self.cell_notation((a0, a1))).collect())
+            .collect();
+
+        let moves: Vec<String> = self.moves.iter()
+            .map(|m| format!("{}:{},{}>{},{}", m.player, m.from.0, m.from.1, m.to.0, m.to.1))
+            .collect();
+        let moves = if moves.is_empty() { "-".to_string() } else { moves.join(";") };
+
+        format!("{} {} {} {} {} {}", map, rows.join("/"), self.playing, self.round, self.last_progress, moves)
+    }
+
+    fn cell_notation(&self, (a0, a1): (usize, usize)) -> String {
+        let begin = self.units_begin.get(a0, a1).unwrap_or(0);
+        let available = self.units_available.get(a0, a1).unwrap_or(0);
+        let moved = self.units_moved.get(a0, a1).unwrap_or(0);
+
+        let mut cell = match (begin, available, moved) {
+            (0, 0, 0) => ".".to_string(),
+            (b, a, 0) if b == a => b.to_string(),
+            (b, a, m) => format!("({}{}{})", b, a, m),
+        };
+
+        if begin != 0 {
+            let label = self.labels.get(a0, a1).unwrap_or(0);
+            if label != 0 {
+                cell.push_str(&format!("@{}", label));
+            }
+
+            let points = self.points.get(a0, a1).unwrap_or(0);
+            if points != self.definition.movement_points {
+                cell.push_str(&format!("+{}", points));
+            }
+        }
+
+        cell
+    }
+}
+
+impl MAPFEnvironment {
+    /// Restores a state written by `MAPFState::to_notation` on this map
+    pub fn parse_notation(&self, notation: &str) -> Result<MAPFState, NotationError> {
+        let definition = &self.definition;
+        let shape = definition.shape;
+        let mut cursor = Cursor { chars: notation.char_indices().peekable(), offset: 0 };
+
+        let mut map = String::new();
+        while let Some(c) = cursor.peek().filter(|&c| c != ' ') {
+            map.push(c);
+            cursor.bump();
+        }
+        let name = definition.metadata.name.as_deref().map_or("-".to_string(), |name| name.replace(' ', "_"));
+        if map != name {
+            return Err(NotationError::WrongMap(map));
+        }
+        cursor.expect(' ')?;
+
+        let mut state = self.get_initial_state();
+        state.units_begin = SparseMatrix2D::new_by_shape(shape);
+        state.units_available = SparseMatrix2D::new_by_shape(shape);
+        state.units_moved = SparseMatrix2D::new_by_shape(shape);
+        state.labels = SparseMatrix2D::new_by_shape(shape);
+        state.points = SparseMatrix2D::new_by_shape(shape);
+
+        let mut a0 = 0;
+        let mut a1 = 0;
+
+        loop {
+            match cursor.peek() {
+                Some('/') => {
+                    cursor.bump();
+                    if a1 != shape.1 {
+                        return Err(NotationError::WrongShape(a0 + 1, a1, shape.0, shape.1));
+                    }
+                    (a0, a1) = (a0 + 1, 0);
+                    continue;
+                }
+                Some(' ') | None => break,
+                _ => {}
+            }
+
+            let (begin, available, moved) = match cursor.bump() {
+                Some('.') => (0, 0, 0),
+                Some('(') => {
+                    let layers = (cursor.digit()?, cursor.digit()?, cursor.digit()?);
+                    cursor.expect(')')?;
+                    layers
+                }
+                Some(c) if c.is_ascii_digit() => (c as u8 - b'0', c as u8 - b'0', 0),
+                _ => return Err(NotationError::Syntax("invalid cell".to_string(), cursor.offset)),
+            };
+
+            if a0 >= shape.0 || a1 >= shape.1 {
+                return Err(NotationError::WrongShape(a0 + 1, a1 + 1, shape.0, shape.1));
+            }
+
+            let blocked = definition.obstacles.get(a0, a1).unwrap_or(0) != 0;
+            if [begin, available, moved].iter().any(|&p| p != 0 && (blocked || !definition.players.contains(&p))) {
+                return Err(NotationError::InvalidUnit((a0, a1)));
+            }
+
+            state.units_begin.insert(a0, a1, begin);
+            state.units_available.insert(a0, a1, available);
+            state.units_moved.insert(a0, a1, moved);
+
+            if begin != 0 {
+                state.points.insert(a0, a1, definition.movement_points);
+            }
+            if cursor.peek() == Some('@') {
+                cursor.bump();
+                state.labels.insert(a0, a1, cursor.number()?);
+            }
+            if cursor.peek() == Some('+') {
+                cursor.bump();
+                state.points.insert(a0, a1, cursor.number()?);
+            }
+
+            a1 += 1;
+        }
+
+        if a0 + 1 != shape.0 || a1 != shape.1 {
+            return Err(NotationError::WrongShape(a0 + 1, a1, shape.0, shape.1));
+        }
+
+        cursor.expect(' ')?;
+        state.playing = cursor.digit()?;
+        if !definition.players.contains(&state.playing) {
+            return Err(NotationError::Syntax("player to move is not on the map".to_string(), cursor.offset));
+        }
+        cursor.expect(' ')?;
+        state.round = cursor.number()?;
+        cursor.expect(' ')?;
+        state.last_progress = cursor.number()?;
+        cursor.expect(' ')?;
+
+        if cursor.peek() == Some('-') {
+            cursor.bump();
+        } else {
+            loop {
+                let player = cursor.digit()?;
+                cursor.expect(':')?;
+                let from = cursor.cell()?;
+                cursor.expect('>')?;
+                let to = cursor.cell()?;
+
+                let agent = state.labels.get(from.0, from.1).unwrap_or(0);
+                state.moves.push(UnitMove { player, agent, from, to });
+
+                if cursor.peek() != Some(';') {
+                    break;
+                }
+                cursor.bump();
+            }
+        }
+
+        if cursor.peek().is_some() {
+            return Err(cursor.error("unexpected trailing characters"));
+        }
+
+        state.moves.sort();
+        state.zobrist = state.compute_zobrist();
+        state.history = History::default();
+        state.history.push(state.zobrist);
+
+        Ok(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::deps::state_definition::StateEnvironment;
+    use crate::mapf::action::MAPFAction::{Commit, Move};
+    use crate::mapf::environment::{MAPFEnvironment, TurnMode};
+    use crate::mapf::notation::NotationError;
+
+    #[test]
+    fn test_notation_round_trip() {
+        let env = MAPFEnvironment::new_from_file("./maps/test_duel.test.txt").unwrap();
+        let mut state = env.get_initial_state();
+        assert_eq!(state.to_notation(), "- .1.2./..... 1 0 0 -");
+
+        state = env.next(&state, &Move((0, 1), (1, 1)));
+        assert_eq!(state.to_notation(), "- .(100).2./.(001)... 1 0 0 1:0,1>1,1");
+        assert_eq!(env.parse_notation(&state.to_notation()).unwrap(), state);
+
+        state = env.next(&state, &Commit);
+        let parsed = env.parse_notation(&state.to_notation()).unwrap();
+        assert_eq!(parsed, state);
+        assert_eq!(parsed.zobrist, state.zobrist);
+        assert_eq!(parsed.round, state.round);
+    }
+
+    #[test]
+    fn test_notation_of_labelled_and_hidden_moves() {
+        let env = MAPFEnvironment::new_from_file("./maps/test_swamp.test.txt")
+            .unwrap()
+            .labelled()
+            .unwrap()
+            .with_turn_mode(TurnMode::Simultaneous);
+        let mut state = env.get_initial_state();
+
+        for action in [Move((1, 0), (1, 1)), Commit, Move((2, 3), (2, 2))] {
+            state = env.next(&state, &action);
+            let notation = state.to_notation();
+
+            assert_eq!(env.parse_notation(&notation).unwrap(), state, "{}", notation);
+            assert_eq!(env.parse_notation(&notation).unwrap().to_notation(), notation);
+        }
+
+        assert!(state.to_notation().contains("1@1"));
+        assert!(state.to_notation().contains("1:1,0>1,1"));
+    }
+
+    #[test]
+    fn test_notation_errors() {
+        let env = MAPFEnvironment::new_from_file("./maps/test_duel.test.txt").unwrap();
+
+        assert_eq!(env.parse_notation("duel .1.2./..... 1 0 0 -"), Err(NotationError::WrongMap("duel".to_string())));
+        assert_eq!(env.parse_notation("- .1.2/..... 1 0 0 -"), Err(NotationError::WrongShape(1, 4, 2, 5)));
+        assert_eq!(env.parse_notation("- .1.2./....3 1 0 0 -"), Err(NotationError::InvalidUnit((1, 4))));
+        assert_eq!(env.parse_notation("- .1.2./..x.. 1 0 0 -"), Err(NotationError::Syntax("invalid cell".to_string(), 11)));
+        assert_eq!(env.parse_notation("- .1.2./..... 1 0 0 1:0,1"), Err(NotationError::Syntax("expected '>'".to_string(), 26)));
+    }
+}
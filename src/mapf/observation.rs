@@ -0,0 +1,238 @@
+use crate::deps::sparse::SparseMatrix2D;
+use crate::mapf::collision::Clash;
+use crate::mapf::environment::MAPFEnvironment;
+use crate::mapf::state::MAPFState;
+use crate::mapf::zobrist;
+use std::borrow::Cow;
+
+/// What a single player knows about the game: its own units, every unit within sight of them and the number
+/// of units every player has left.
+#[derive(Clone, Debug)]
+pub struct Observation<'a> {
+    pub player: u8,
+    /// Cells seen by the player, marked with 1, `None` when the whole map is visible
+    pub visible: Option<SparseMatrix2D>,
+    /// The state with units outside of `visible` and moves of the other players removed, borrowed when nothing
+    /// had to be removed
+    pub state: Cow<'a, MAPFState>,
+    /// Surviving units, indexed by player id
+    pub units: Vec<u64>,
+}
+
+impl MAPFEnvironment {
+    /// Masks `state` down to what `player` can see. Without a vision radius the whole map is visible,
+    /// only the moves other players made this round stay hidden.
+    pub fn observe<'a>(&self, state: &'a MAPFState, player: u8) -> Observation<'a> {
+        let mut units = vec![0; self.definition.goals_num.len()];
+        for &owner in state.units_begin.data.iter().flatten().flatten() {
+            if let Some(count) = units.get_mut(owner as usize).filter(|_| owner != 0) {
+                *count += 1;
+            }
+        }
+
+        let Some(visible) = self.visible_cells(&state.units_begin, player) else {
+            if state.moves.iter().all(|m| m.player == player) {
+                return Observation { player, visible: None, state: Cow::Borrowed(state), units };
+            }
+
+            let mut observed = state.clone();
+            for m in observed.moves.iter().filter(|m| m.player != player) {
+                observed.zobrist ^= zobrist::move_key(m);
+            }
+            observed.moves.retain(|m| m.player == player);
+
+            return Observation { player, visible: None, state: Cow::Owned(observed), units };
+        };
+
+        let seen = |a0: usize, a1: usize| visible.get(a0, a1).unwrap_or(0) != 0;
+        let mut observed = state.clone();
+
+        for (a0, a1, owner) in state.units_begin.get_nnz() {
+            if owner != player && !seen(a0, a1) {
+                observed.units_begin.insert(a0, a1, 0);
+                observed.labels.insert(a0, a1, 0);
+                observed.points.insert(a0, a1, 0);
+            }
+        }
+
+        for matrix in [&mut observed.units_available, &mut observed.units_moved] {
+            for (a0, a1, owner) in matrix.get_nnz() {
+                if owner != player && !seen(a0, a1) {
+                    matrix.insert(a0, a1, 0);
+                }
+            }
+        }
+
+        observed.moves.retain(|m| m.player == player);
+        observed.clashes.retain(|clash| match clash {
+            Clash::Vertex { cell, .. } => seen(cell.0, cell.1),
+            Clash::Edge { cells: (from, to), .. } => seen(from.0, from.1) || seen(to.0, to.1),
+        });
+
+        observed.zobrist = observed.compute_zobrist();
+
+        Observation { player, visible: Some(visible), state: Cow::Owned(observed), units }
+    }
+
+    /// Cells within the vision radius of a unit of `player` that are not hidden behind obstacles, `None` without
+    /// a vision radius
+    fn visible_cells(&self, positions: &SparseMatrix2D, player: u8) -> Option<SparseMatrix2D> {
+        let shape = self.definition.shape;
+        let mut visible = SparseMatrix2D::new_by_shape(shape);
+        let radius = self.definition.vision? as isize;
+
+        for (u0, u1, owner) in positions.get_nnz() {
+            if owner != player {
+                continue;
+            }
+
+            for d0 in -radius..=radius {
+                for d1 in -radius..=radius {
+                    let (c0, c1) = (u0 as isize + d0, u1 as isize + d1);
+
+                    if d0 * d0 + d1 * d1 > radius * radius
+                        || c0 < 0 || c1 < 0 || c0 as usize >= shape.0 || c1 as usize >= shape.1
+                    {
+                        continue;
+                    }
+
+                    let cell = (c0 as usize, c1 as usize);
+                    if self.definition.line_of_sight((u0, u1), cell) {
+                        visible.insert(cell.0, cell.1, 1);
+                    }
+                }
+            }
+        }
+
+        Some(visible)
+    }
+}
+
+impl Observation<'_> {
+    pub fn is_visible(&self, cell: (usize, usize)) -> bool {
+        self.visible.as_ref().is_none_or(|visible| visible.get(cell.0, cell.1).unwrap_or(0) != 0)
+    }
+
+    /// A full state consistent with the observation, for AIs that search over complete states. Hidden units
+    /// are guessed to stand on unseen starting positions of their player, or on the first unseen free cell.
+    pub fn determinize(&self) -> MAPFState {
+        let mut state = self.state.as_ref().clone();
+        let definition = state.definition.clone();
+        let (height, width) = definition.shape;
+
+        for (player, &count) in self.units.iter().enumerate().skip(1) {
+            let player = player as u8;
+            let mut missing = count.saturating_sub(state.units_begin.count_value(player));
+
+            let starts = definition.starting_positions.get_nnz()
+                .into_iter()
+                .filter(|&(_, _, p)| p == player)
+                .map(|(a0, a1, _)| (a0, a1));
+            let anywhere = (0..height).flat_map(|a0| (0..width).map(move |a1| (a0, a1)));
+
+            // Labelled units are guessed in id order among the agents not seen
+            let mut agents = definition.agents.iter()
+                .enumerate()
+                .filter(|(idx, agent)| agent.player == player && state.labels.count_value(*idx as u16 + 1) == 0)
+                .map(|(idx, _)| idx as u16 + 1)
+                .collect::<Vec<u16>>()
+                .into_iter();
+
+            for (a0, a1) in starts.chain(anywhere) {
+                if missing == 0 {
+                    break;
+                }
+
+                if self.is_visible((a0, a1))
+                    || definition.obstacles.get(a0, a1).unwrap_or(0) != 0
+                    || state.units_begin.get(a0, a1).unwrap_or(0) != 0
+                {
+                    continue;
+                }
+
+                state.units_begin.insert(a0, a1, player);
+                state.units_available.insert(a0, a1, player);
+                state.points.insert(a0, a1, definition.movement_points);
+                state.labels.insert(a0, a1, agents.next().unwrap_or(0));
+                missing -= 1;
+            }
+        }
+
+        state.zobrist = state.compute_zobrist();
+        state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::deps::state_definition::StateEnvironment;
+    use std::borrow::Cow;
+    use crate::mapf::action::MAPFAction;
+    use crate::mapf::environment::{MAPFEnvironment, TurnMode};
+
+    #[test]
+    fn test_full_vision() {
+        let env = MAPFEnvironment::new_from_file("./maps/test_hall_run.test.txt").unwrap();
+        let state = env.get_initial_state();
+        let observation = env.observe(&state, 1);
+
+        assert!(observation.is_visible((1, 1)));
+        assert!(observation.visible.is_none());
+        assert!(matches!(observation.state, Cow::Borrowed(_)));
+        assert_eq!(observation.determinize(), state);
+    }
+
+    #[test]
+    fn test_vision_radius() {
+        let env = MAPFEnvironment::new_from_file("./maps/test_hall_run.test.txt")
+            .unwrap()
+            .with_vision(Some(2));
+        let state = env.get_initial_state();
+        let observation = env.observe(&state, 1);
+
+        assert!(observation.is_visible((1, 2)));
+        assert!(!observation.is_visible((1, 1)));
+        assert_eq!(observation.state.units_begin.get(1, 4).unwrap(), 1);
+        assert_eq!(observation.state.units_begin.get(1, 1).unwrap_or(0), 0);
+        assert_eq!(observation.units, vec![0, 1, 1]);
+
+        // The hidden unit is guessed to be on its starting position
+        assert_eq!(observation.determinize().units_begin.get_nnz(), state.units_begin.get_nnz());
+
+        let env = env.with_vision(Some(3));
+        assert!(env.observe(&env.get_initial_state(), 1).is_visible((1, 1)));
+    }
+
+    #[test]
+    fn test_line_of_sight() {
+        use std::fs;
+
+        let path = std::env::temp_dir().join("mapf_test_line_of_sight.txt");
+        fs::write(&path, "vision: 5\n---\n1#.2\nA..B\n").unwrap();
+        let env = MAPFEnvironment::new_from_file(&path).unwrap();
+        let state = env.get_initial_state();
+        let observation = env.observe(&state, 1);
+
+        assert!(observation.is_visible((0, 1)));
+        assert!(observation.is_visible((1, 0)));
+        assert!(!observation.is_visible((0, 3)));
+        assert_eq!(observation.state.units_begin.get(0, 3).unwrap_or(0), 0);
+    }
+
+    #[test]
+    fn test_hidden_moves() {
+        let env = MAPFEnvironment::new_from_file("./maps/test_duel.test.txt")
+            .unwrap()
+            .with_turn_mode(TurnMode::Simultaneous);
+        let mut state = env.get_initial_state();
+
+        state = env.next(&state, &MAPFAction::Move((0, 1), (0, 2)));
+        state = env.next(&state, &MAPFAction::Commit);
+
+        assert_eq!(state.moves.len(), 1);
+        let hidden = env.observe(&state, 2);
+        assert!(hidden.state.moves.is_empty());
+        assert_eq!(hidden.state.zobrist, hidden.state.compute_zobrist());
+        assert_eq!(env.observe(&state, 1).state.moves, state.moves);
+    }
+}
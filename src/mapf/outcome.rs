@@ -0,0 +1,41 @@
+use crate::deps::state_definition::StateStatus;
+
+/// Why a game ended
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum TerminationReason {
+    /// The winner occupies all of its goals
+    AllGoalsReached,
+    /// The winner is the only player with units left
+    Elimination,
+    /// No player has any units left
+    MutualElimination,
+    /// None of the surviving players has a goal left to reach
+    NoGoalsLeft,
+    TurnLimit,
+    NoProgress,
+    Repetition,
+}
+
+/// Status of a game together with the statistics it was decided on
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Outcome {
+    pub status: StateStatus,
+    /// `None` while the game is running
+    pub reason: Option<TerminationReason>,
+    /// Number of completed rounds
+    pub round: u32,
+    /// Surviving units, indexed by player id
+    pub units: Vec<u64>,
+    /// Own goals occupied, indexed by player id
+    pub goals_held: Vec<u64>,
+}
+
+impl Outcome {
+    pub fn units_of(&self, player: u8) -> u64 {
+        self.units.get(player as usize).copied().unwrap_or(0)
+    }
+
+    pub fn goals_held_by(&self, player: u8) -> u64 {
+        self.goals_held.get(player as usize).copied().unwrap_or(0)
+    }
+}
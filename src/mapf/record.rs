@@ -0,0 +1,330 @@
+use crate::deps::state_definition::{FallibleStateEnvironment, StateEnvironment};
+use crate::mapf::action::MAPFAction;
+use crate::mapf::definition::Agent;
+use crate::mapf::environment::{IllegalAction, MAPFEnvironment, MAPFErrorLoad};
+use crate::mapf::state::MAPFState;
+use std::fmt::{self, Formatter};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use std::{fs, io};
+use thiserror::Error;
+
+const VERSION: u32 = 1;
+
+/// A single action of a recorded game, `None` when the player forfeited instead
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RecordEntry {
+    pub player: u8,
+    pub action: Option<MAPFAction>,
+    /// Time the player took to decide
+    pub elapsed: Duration,
+}
+
+/// Everything needed to replay a game: the map with its rules embedded, the players and every action taken.
+///
+/// Records are stored as text. A header of `key: value` lines is followed by the map, in the text map format,
+/// and by the actions, one per line as the player, `move a0,a1 b0,b1`, `commit` or `forfeit`, and the time
+/// taken in microseconds:
+///
+/// ```text
+/// record: 1
+/// seed: 7
+/// player: 1 GreedyAI
+/// player: 2 RandomAI
+/// === map
+/// 1..A
+/// B..2
+/// === actions
+/// 1 move 0,0 0,1 120us
+/// 1 commit 3us
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GameRecord {
+    /// The map in the text format, including every rule that differs from the defaults
+    pub map: String,
+    /// Where the map was loaded from, for reference only
+    pub map_file: Option<PathBuf>,
+    /// Agents in labelled mode, these are not part of the text map format
+    pub agents: Vec<Agent>,
+    /// Names of the players' AIs, with their parameters
+    pub players: Vec<(u8, String)>,
+    /// Seed the AIs were seeded with by `evaluate_ai`, playing again with it repeats the game as long as the AIs
+    /// do not depend on timing
+    pub seed: Option<u64>,
+    pub entries: Vec<RecordEntry>,
+}
+
+#[derive(Debug, Error)]
+pub enum RecordError {
+    #[error("Could not read the record: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Invalid map in the record: {0}")]
+    Map(#[from] MAPFErrorLoad),
+
+    #[error("{0} on line {1}")]
+    Syntax(String, usize),
+
+    #[error("Action {0} can not be replayed: {1}")]
+    Replay(usize, IllegalAction),
+
+    #[error("Action {0} was recorded for player {1}, but player {2} is to move")]
+    WrongPlayer(usize, u8, u8),
+}
+
+impl GameRecord {
+    pub fn new(env: &MAPFEnvironment) -> Self {
+        GameRecord {
+            map: env.to_text_string(),
+            agents: env.definition.agents.clone(),
+            ..GameRecord::default()
+        }
+    }
+
+    /// The environment the game was played in
+    pub fn environment(&self) -> Result<MAPFEnvironment, RecordError> {
+        let env = MAPFEnvironment::from_text(&self.map)?;
+
+        if self.agents.is_empty() {
+            Ok(env)
+        } else {
+            Ok(env.with_agents(self.agents.clone())?)
+        }
+    }
+
+    /// The initial state followed by the state after every recorded action
+    pub fn states(&self) -> Result<Vec<MAPFState>, RecordError> {
+        let env = self.environment()?;
+        let mut states = vec![env.get_initial_state()];
+
+        for (idx, entry) in self.entries.iter().enumerate() {
+            let state = states.last().unwrap();
+
+            if entry.player != state.playing {
+                return Err(RecordError::WrongPlayer(idx, entry.player, state.playing));
+            }
+
+            let next = match &entry.action {
+                Some(action) => env.try_next(state, action).map_err(|e| RecordError::Replay(idx, e))?,
+                None => env.forfeit(state),
+            };
+            states.push(next);
+        }
+
+        Ok(states)
+    }
+
+    pub fn save_to_file<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+
+    pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self, RecordError> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn parse(content: &str) -> Result<Self, RecordError> {
+        let mut record = GameRecord::default();
+        let mut lines = content.lines().enumerate().map(|(idx, line)| (idx + 1, line));
+        let syntax = |message: &str, line_number: usize| RecordError::Syntax(message.to_string(), line_number);
+
+        for (line_number, line) in lines.by_ref() {
+            if line.trim() == "=== map" {
+                break;
+            }
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let (key, value) = line.split_once(':').ok_or_else(|| syntax("expected 'key: value'", line_number))?;
+            let value = value.trim();
+            let invalid = || syntax(&format!("invalid value for '{}'", key.trim()), line_number);
+
+            match key.trim() {
+                "record" => {
+                    if value.parse::<u32>().ok() != Some(VERSION) {
+                        return Err(syntax("unsupported record version", line_number));
+                    }
+                }
+                "map_file" => record.map_file = Some(PathBuf::from(value)),
+                "seed" => record.seed = Some(value.parse().map_err(|_| invalid())?),
+                "player" => {
+                    let (player, name) = value.split_once(' ').unwrap_or((value, ""));
+                    record.players.push((player.parse().map_err(|_| invalid())?, name.trim().to_string()));
+                }
+                "agent" => {
+                    let fields: Vec<&str> = value.split_whitespace().collect();
+                    let [player, start, goal] = fields[..] else { return Err(invalid()) };
+                    record.agents.push(Agent {
+                        player: player.parse().map_err(|_| invalid())?,
+                        start: parse_cell(start).ok_or_else(invalid)?,
+                        goal: parse_cell(goal).ok_or_else(invalid)?,
+                    });
+                }
+                _ => return Err(syntax(&format!("unknown key '{}'", key.trim()), line_number)),
+            }
+        }
+
+        let mut map = Vec::new();
+        for (_, line) in lines.by_ref() {
+            if line.trim() == "=== actions" {
+                break;
+            }
+            map.push(line);
+        }
+        record.map = map.join("\n") + "\n";
+
+        for (line_number, line) in lines {
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let invalid = || syntax("invalid action", line_number);
+
+            let (player, action, elapsed) = match fields[..] {
+                [player, "move", from, to, elapsed] => {
+                    let action = MAPFAction::Move(parse_cell(from).ok_or_else(invalid)?, parse_cell(to).ok_or_else(invalid)?);
+                    (player, Some(action), elapsed)
+                }
+                [player, "commit", elapsed] => (player, Some(MAPFAction::Commit), elapsed),
+                [player, "forfeit", elapsed] => (player, None, elapsed),
+                _ => return Err(invalid()),
+            };
+
+            let micros = elapsed.strip_suffix("us").and_then(|micros| micros.parse().ok()).ok_or_else(invalid)?;
+            record.entries.push(RecordEntry {
+                player: player.parse().map_err(|_| invalid())?,
+                action,
+                elapsed: Duration::from_micros(micros),
+            });
+        }
+
+        Ok(record)
+    }
+}
+
+fn parse_cell(text: &str) -> Option<(usize, usize)> {
+    let (a0, a1) = text.split_once(',')?;
+    Some((a0.parse().ok()?, a1.parse().ok()?))
+}
+
+impl fmt::Display for GameRecord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "record: {}", VERSION)?;
+
+        if let Some(map_file) = &self.map_file {
+            writeln!(f, "map_file: {}", map_file.display())?;
+        }
+        if let Some(seed) = self.seed {
+            writeln!(f, "seed: {}", seed)?;
+        }
+        for (player, name) in &self.players {
+            writeln!(f, "player: {} {}", player, name)?;
+        }
+        for agent in &self.agents {
+            writeln!(f, "agent: {} {},{} {},{}", agent.player, agent.start.0, agent.start.1, agent.goal.0, agent.goal.1)?;
+        }
+
+        writeln!(f, "=== map")?;
+        write!(f, "{}", self.map)?;
+        writeln!(f, "=== actions")?;
+
+        for entry in &self.entries {
+            write!(f, "{} ", entry.player)?;
+            match entry.action {
+                Some(MAPFAction::Move(from, to)) => write!(f, "move {},{} {},{}", from.0, from.1, to.0, to.1)?,
+                Some(MAPFAction::Commit) => write!(f, "commit")?,
+                None => write!(f, "forfeit")?,
+            }
+            writeln!(f, " {}us", entry.elapsed.as_micros())?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ai::greedy::GreedyAI;
+    use crate::ai::random_ai::RandomAI;
+    use crate::ai::AI;
+    use crate::deps::state_definition::StateEnvironment;
+    use crate::loops::{args, evaluate_ai, EvaluateAIParams};
+    use crate::mapf::action::MAPFAction;
+    use crate::mapf::environment::MAPFEnvironment;
+    use crate::mapf::record::{GameRecord, RecordEntry, RecordError};
+    use std::time::Duration;
+
+    #[test]
+    fn test_record_round_trip() {
+        let env = MAPFEnvironment::new_from_file("./maps/test_duel.test.txt").unwrap().labelled().unwrap();
+        let mut record = GameRecord::new(&env);
+        record.seed = Some(7);
+        record.players = vec![(1, "GreedyAI".to_string()), (2, "RandomAI".to_string())];
+        record.entries = vec![
+            RecordEntry { player: 1, action: Some(MAPFAction::Move((0, 1), (0, 2))), elapsed: Duration::from_micros(120) },
+            RecordEntry { player: 1, action: Some(MAPFAction::Commit), elapsed: Duration::from_micros(3) },
+            RecordEntry { player: 2, action: None, elapsed: Duration::from_micros(40) },
+        ];
+
+        let path = std::env::temp_dir().join("mapf_test_record.txt");
+        record.save_to_file(&path).unwrap();
+        let loaded = GameRecord::load_from_file(&path).unwrap();
+        assert_eq!(loaded, record);
+
+        let state = loaded.states().unwrap().pop().unwrap();
+        let expected = env.forfeit(&env.next(&env.next(&env.get_initial_state(), &record.entries[0].action.clone().unwrap()), &MAPFAction::Commit));
+        assert_eq!(state, expected);
+        assert_eq!(loaded.states().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_replay_errors() {
+        let env = MAPFEnvironment::new_from_file("./maps/test_duel.test.txt").unwrap();
+        let mut record = GameRecord::new(&env);
+
+        record.entries = vec![RecordEntry { player: 2, action: Some(MAPFAction::Commit), elapsed: Duration::ZERO }];
+        assert!(matches!(record.states(), Err(RecordError::WrongPlayer(0, 2, 1))));
+
+        record.entries[0] = RecordEntry { player: 1, action: Some(MAPFAction::Move((0, 0), (0, 1))), elapsed: Duration::ZERO };
+        assert!(matches!(record.states(), Err(RecordError::Replay(0, _))));
+
+        let broken = record.to_string().replace("1 move 0,0 0,1", "1 jump 0,0 0,1");
+        assert!(matches!(GameRecord::parse(&broken), Err(RecordError::Syntax(_, 6))));
+    }
+
+    #[test]
+    fn test_evaluate_ai_records() {
+        let env = MAPFEnvironment::new_from_file("./maps/test_hall_run.test.txt").unwrap();
+        let path = std::env::temp_dir().join("mapf_test_evaluate_record.txt");
+        let params = args!(EvaluateAIParams, max_iters: 500, record: Some(path.clone()), seed: Some(3));
+
+        let outcome = evaluate_ai(&env, vec![Box::new(GreedyAI::new()), Box::new(RandomAI::new())], params).unwrap();
+        let record = GameRecord::load_from_file(&path).unwrap();
+
+        assert_eq!(record.seed, Some(3));
+        assert_eq!(record.players, vec![(1, "GreedyAI".to_string()), (2, "RandomAI".to_string())]);
+        assert_eq!(env.get_outcome(&record.states().unwrap().pop().unwrap()), outcome);
+
+        // The third player of the map has no actor
+        let three_way = MAPFEnvironment::new_from_file("./maps/test_three_way.test.txt").unwrap();
+        let actors: Vec<Box<dyn AI>> = vec![Box::new(GreedyAI::new()), Box::new(RandomAI::new())];
+        let error = evaluate_ai(&three_way, actors, EvaluateAIParams::default()).unwrap_err();
+        assert_eq!(error.to_string(), "The map has 3 players, but 2 actors were given");
+    }
+
+    #[test]
+    fn test_seed_repeats_game() {
+        let env = MAPFEnvironment::new_from_file("./maps/test_hall_run.test.txt").unwrap();
+        let actions = || {
+            let path = std::env::temp_dir().join("mapf_test_seed_record.txt");
+            let params = args!(EvaluateAIParams, max_iters: 200, record: Some(path.clone()), seed: Some(7));
+            evaluate_ai(&env, vec![Box::new(RandomAI::new()), Box::new(RandomAI::new())], params).unwrap();
+
+            let record = GameRecord::load_from_file(&path).unwrap();
+            record.entries.into_iter().map(|entry| (entry.player, entry.action)).collect::<Vec<_>>()
+        };
+
+        assert_eq!(actions(), actions());
+    }
+}
@@ -1,4 +1,6 @@
 use crate::deps::sparse::SparseMatrix2D;
+use crate::mapf::action::UnitMove;
+use crate::mapf::collision::Clash;
 use crate::mapf::definition::MAPFDefinition;
 use crate::mapf::state::SpecialState::No;
 use derive_more::Display;
@@ -15,12 +17,131 @@ pub struct MAPFState {
     pub units_available: SparseMatrix2D,
     pub units_moved: SparseMatrix2D,
 
+    /// Agent ids of the units in `units_begin`, empty unless the definition is labelled
+    pub labels: SparseMatrix2D<u16>,
+    /// Movement points saved up by the units in `units_begin`
+    pub points: SparseMatrix2D,
+
     pub playing: u8,
 
+    /// Moves made since the round started, kept sorted so move order does not matter.
+    /// In simultaneous mode this also holds moves of players that already committed this round,
+    /// which are deliberately not shown in `units_available` / `units_moved`.
+    pub moves: Vec<UnitMove>,
+
+    /// Clashes that happened when the last round was resolved. Not part of the state identity.
+    pub clashes: Vec<Clash>,
+
+    /// Number of completed rounds
+    pub round: u32,
+    /// Round count at the time a unit last reached an own goal or was destroyed
+    pub last_progress: u32,
+    /// Zobrist keys at the start of every turn since a unit was last destroyed
+    pub history: History,
+
+    /// Zobrist key of the units, labels, movement points, moves of the round and the player to move, see
+    /// `compute_zobrist`
+    pub zobrist: u64,
+
     pub special_state: SpecialState
 }
 
-#[derive(Clone, PartialOrd, PartialEq, Hash, Debug, Display)]
+/// What `MAPFEnvironment::undo` needs to take back an action applied with `MAPFEnvironment::apply`
+#[derive(Clone, Debug)]
+pub enum Undo {
+    /// Index of the unit move in `MAPFState::moves`
+    Move(usize),
+    /// Player that handed a simultaneous round on and the key before, its moves stay in `MAPFState::moves`
+    Handover(u8, u64),
+    /// What resolving the round changed
+    Commit(Box<CommitUndo>),
+}
+
+/// The parts of a state changed by resolving a round, as they were before
+#[derive(Clone, Debug)]
+pub struct CommitUndo {
+    /// Cells of the moves and of the units that gained points, restored in reverse order
+    pub(crate) cells: Vec<SavedCell>,
+    pub(crate) moves: Vec<UnitMove>,
+    pub(crate) clashes: Vec<Clash>,
+    pub(crate) playing: u8,
+    pub(crate) round: u32,
+    pub(crate) last_progress: u32,
+    pub(crate) zobrist: u64,
+    /// History replaced once a unit was destroyed, otherwise the commit only added a key to it
+    pub(crate) history: Option<History>,
+}
+
+/// A cell of every matrix of the state tracking units
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct SavedCell {
+    cell: (usize, usize),
+    begin: u8,
+    available: u8,
+    moved: u8,
+    label: u16,
+    points: u8,
+}
+
+/// Zobrist keys of past turns, newest first. States share the keys they have in common, so that adding a key
+/// does not copy the others.
+#[derive(Clone, Default)]
+pub struct History(Option<Arc<HistoryNode>>);
+
+struct HistoryNode {
+    key: u64,
+    previous: History,
+}
+
+impl History {
+    pub fn push(&mut self, key: u64) {
+        let previous = std::mem::take(self);
+        self.0 = Some(Arc::new(HistoryNode { key, previous }));
+    }
+
+    /// Removes the newest key
+    pub fn pop(&mut self) {
+        if let Some(node) = self.0.take() {
+            *self = node.previous.clone();
+        }
+    }
+
+    pub fn last(&self) -> Option<u64> {
+        self.0.as_ref().map(|node| node.key)
+    }
+
+    /// Keys from the newest to the oldest
+    pub fn iter(&self) -> impl Iterator<Item = u64> + '_ {
+        std::iter::successors(self.0.as_deref(), |node| node.previous.0.as_deref()).map(|node| node.key)
+    }
+}
+
+impl Drop for History {
+    // Dropping node by node, a long history would otherwise overflow the stack
+    fn drop(&mut self) {
+        let mut next = self.0.take();
+        while let Some(node) = next {
+            next = match Arc::try_unwrap(node) {
+                Ok(mut node) => node.previous.0.take(),
+                Err(_) => None,
+            };
+        }
+    }
+}
+
+impl PartialEq for History {
+    fn eq(&self, other: &Self) -> bool {
+        self.iter().eq(other.iter())
+    }
+}
+
+impl Debug for History {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+#[derive(Clone, Copy, PartialOrd, PartialEq, Eq, Hash, Debug, Display)]
 pub enum SpecialState {
     No,
     AnyGoal,
@@ -33,10 +154,15 @@ impl PartialEq for MAPFState {
             return self.special_state == other.special_state;
         }
 
-        self.units_available == other.units_available
+        // Different keys tell most states apart without looking at the matrices
+        self.zobrist == other.zobrist
+            && self.units_available == other.units_available
             && self.units_moved == other.units_moved
             && self.units_begin == other.units_begin
+            && self.labels == other.labels
+            && self.points == other.points
             && self.playing == other.playing
+            && self.moves == other.moves
     }
 }
 
@@ -50,10 +176,40 @@ impl Hash for MAPFState {
             return;
         }
 
-        self.units_available.hash(state);
-        self.units_moved.hash(state);
-        self.units_begin.hash(state);
-        self.playing.hash(state);
+        self.zobrist.hash(state);
+    }
+}
+
+impl MAPFState {
+    pub fn idle_rounds(&self) -> u32 {
+        self.round.saturating_sub(self.last_progress)
+    }
+
+    pub fn repetitions(&self) -> u32 {
+        match self.history.last() {
+            Some(key) => self.history.iter().filter(|&k| k == key).count() as u32,
+            None => 0,
+        }
+    }
+
+    pub(crate) fn save_cell(&self, (a0, a1): (usize, usize)) -> SavedCell {
+        SavedCell {
+            cell: (a0, a1),
+            begin: self.units_begin.get(a0, a1).unwrap_or(0),
+            available: self.units_available.get(a0, a1).unwrap_or(0),
+            moved: self.units_moved.get(a0, a1).unwrap_or(0),
+            label: self.labels.get(a0, a1).unwrap_or(0),
+            points: self.points.get(a0, a1).unwrap_or(0),
+        }
+    }
+
+    pub(crate) fn restore_cell(&mut self, saved: &SavedCell) {
+        let (a0, a1) = saved.cell;
+        self.units_begin.insert(a0, a1, saved.begin);
+        self.units_available.insert(a0, a1, saved.available);
+        self.units_moved.insert(a0, a1, saved.moved);
+        self.labels.insert(a0, a1, saved.label);
+        self.points.insert(a0, a1, saved.points);
     }
 }
 
@@ -65,6 +221,9 @@ impl Debug for MAPFState {
             .field("stage0_positions", &self.units_begin.get_nnz())
             .field("stage1_positions", &self.units_available.get_nnz())
             .field("stage2_positions", &self.units_moved.get_nnz())
+            .field("moves", &self.moves)
+            .field("clashes", &self.clashes)
+            .field("round", &self.round)
             .field("special_state", &self.special_state)
             .finish()
     }
@@ -0,0 +1,268 @@
+use crate::deps::sparse::SparseMatrix2D;
+use crate::mapf::definition::MAPFDefinition;
+use crate::mapf::environment::MAPFEnvironment;
+use std::sync::Arc;
+
+/// Symmetries of the grid. Rotations are clockwise, cells are (row, column).
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum Transform {
+    Identity,
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    /// Swaps the left and right side
+    MirrorHorizontal,
+    /// Swaps the top and bottom
+    MirrorVertical,
+    /// Reflection along the main diagonal
+    Transpose,
+    /// Reflection along the other diagonal, completing the symmetries of the square
+    AntiTranspose,
+}
+
+impl Transform {
+    pub const ALL: [Transform; 8] = [
+        Transform::Identity,
+        Transform::Rotate90,
+        Transform::Rotate180,
+        Transform::Rotate270,
+        Transform::MirrorHorizontal,
+        Transform::MirrorVertical,
+        Transform::Transpose,
+        Transform::AntiTranspose,
+    ];
+
+    pub fn shape(self, shape: (usize, usize)) -> (usize, usize) {
+        match self {
+            Transform::Rotate90 | Transform::Rotate270 | Transform::Transpose | Transform::AntiTranspose => (shape.1, shape.0),
+            _ => shape,
+        }
+    }
+
+    /// Where `cell` of a map of `shape` ends up
+    pub fn cell(self, (r, c): (usize, usize), (h, w): (usize, usize)) -> (usize, usize) {
+        match self {
+            Transform::Identity => (r, c),
+            Transform::Rotate90 => (c, h - 1 - r),
+            Transform::Rotate180 => (h - 1 - r, w - 1 - c),
+            Transform::Rotate270 => (w - 1 - c, r),
+            Transform::MirrorHorizontal => (r, w - 1 - c),
+            Transform::MirrorVertical => (h - 1 - r, c),
+            Transform::Transpose => (c, r),
+            Transform::AntiTranspose => (w - 1 - c, h - 1 - r),
+        }
+    }
+
+    pub fn offset(self, (d0, d1): (isize, isize)) -> (isize, isize) {
+        match self {
+            Transform::Identity => (d0, d1),
+            Transform::Rotate90 => (d1, -d0),
+            Transform::Rotate180 => (-d0, -d1),
+            Transform::Rotate270 => (-d1, d0),
+            Transform::MirrorHorizontal => (d0, -d1),
+            Transform::MirrorVertical => (-d0, d1),
+            Transform::Transpose => (d1, d0),
+            Transform::AntiTranspose => (-d1, -d0),
+        }
+    }
+
+    fn matrix<T: Copy + Default + PartialEq>(self, matrix: &SparseMatrix2D<T>) -> SparseMatrix2D<T> {
+        let mut transformed = SparseMatrix2D::new_by_shape(self.shape(matrix.shape));
+
+        for (a0, a1, value) in matrix.get_nnz() {
+            let (t0, t1) = self.cell((a0, a1), matrix.shape);
+            transformed.insert(t0, t1, value);
+        }
+
+        transformed
+    }
+}
+
+impl MAPFDefinition {
+    /// The same map seen through `transform`, the move set is transformed along with the grid
+    pub fn transformed(&self, transform: Transform) -> MAPFDefinition {
+        let mut definition = self.clone();
+
+        definition.shape = transform.shape(self.shape);
+        definition.starting_positions = transform.matrix(&self.starting_positions);
+        definition.obstacles = transform.matrix(&self.obstacles);
+        definition.goals = transform.matrix(&self.goals);
+        definition.costs = transform.matrix(&self.costs);
+
+        // Symmetric move sets are kept as they are, so that they are still recognised by name
+        let offsets: Vec<(isize, isize)> = self.move_set.offsets.iter().map(|&offset| transform.offset(offset)).collect();
+        if !offsets.iter().all(|offset| self.move_set.offsets.contains(offset)) {
+            definition.move_set.offsets = offsets;
+        }
+
+        for agent in definition.agents.iter_mut() {
+            agent.start = transform.cell(agent.start, self.shape);
+            agent.goal = transform.cell(agent.goal, self.shape);
+        }
+
+        definition
+    }
+
+    /// The same map with the units and goals of players `a` and `b` exchanged, so that they also swap their
+    /// places in the turn order
+    pub fn with_swapped_players(&self, a: u8, b: u8) -> MAPFDefinition {
+        let mut definition = self.clone();
+        let swap = |player: u8| if player == a { b } else if player == b { a } else { player };
+
+        for matrix in [&mut definition.starting_positions, &mut definition.goals] {
+            for (a0, a1, player) in matrix.get_nnz() {
+                matrix.insert(a0, a1, swap(player));
+            }
+        }
+
+        let last = a.max(b) as usize;
+        if definition.goals_num.len() <= last {
+            definition.goals_num.resize(last + 1, 0);
+        }
+        definition.goals_num.swap(a as usize, b as usize);
+
+        definition.players = definition.players.iter().map(|&player| swap(player)).collect();
+        definition.players.sort();
+
+        for agent in definition.agents.iter_mut() {
+            agent.player = swap(agent.player);
+        }
+
+        definition
+    }
+}
+
+impl MAPFEnvironment {
+    pub fn with_transform(mut self, transform: Transform) -> Self {
+        self.definition = Arc::new(self.definition.transformed(transform));
+        self
+    }
+
+    pub fn with_swapped_players(mut self, a: u8, b: u8) -> Self {
+        self.definition = Arc::new(self.definition.with_swapped_players(a, b));
+        self
+    }
+
+    /// The map under every symmetry of the grid, each once as it is and once with players 1 and 2 swapped, for
+    /// evaluations that average out the advantage of a side or of moving first
+    pub fn variants(&self) -> Vec<Self> {
+        Transform::ALL.iter()
+            .flat_map(|&transform| {
+                let transformed = self.clone().with_transform(transform);
+                [transformed.clone(), transformed.with_swapped_players(1, 2)]
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ai::greedy::GreedyAI;
+    use crate::ai::AI;
+    use crate::deps::sparse::SparseMatrix2D;
+    use crate::deps::state_definition::StateStatus;
+    use crate::loops::{evaluate_variants, EvaluateAIParams};
+    use crate::mapf::action::{MoveSet, KNIGHT_MOVES};
+    use crate::mapf::definition::Agent;
+    use crate::mapf::environment::MAPFEnvironment;
+    use crate::mapf::transform::Transform;
+
+    fn env(grid: &str) -> MAPFEnvironment {
+        MAPFEnvironment::from_text(grid).unwrap()
+    }
+
+    #[test]
+    fn test_transforms() {
+        let map = env("1.#A\n,.~2\n");
+        let transformed = |transform| map.clone().with_transform(transform).to_string();
+
+        assert_eq!(transformed(Transform::Identity), "1.#A\n,.~2\n");
+        assert_eq!(transformed(Transform::Rotate90), ",1\n..\n~#\n2A\n");
+        assert_eq!(transformed(Transform::Rotate180), "2~.,\nA#.1\n");
+        assert_eq!(transformed(Transform::Rotate270), "A2\n#~\n..\n1,\n");
+        assert_eq!(transformed(Transform::MirrorHorizontal), "A#.1\n2~.,\n");
+        assert_eq!(transformed(Transform::MirrorVertical), ",.~2\n1.#A\n");
+        assert_eq!(transformed(Transform::Transpose), "1,\n..\n#~\nA2\n");
+        assert_eq!(transformed(Transform::AntiTranspose), "2A\n~#\n..\n,1\n");
+
+        let twice = map.clone().with_transform(Transform::Rotate90).with_transform(Transform::Rotate270);
+        assert_eq!(twice.to_string(), map.to_string());
+        assert_eq!(twice.definition.costs, map.definition.costs);
+    }
+
+    #[test]
+    fn test_swapped_players() {
+        let map = env("1.AA\nB..2\n").with_swapped_players(1, 2);
+
+        assert_eq!(map.to_string(), "2.BB\nA..1\n");
+        assert_eq!(map.definition.goals_num, vec![0, 1, 2]);
+        assert_eq!(map.definition.players, vec![1, 2]);
+    }
+
+    #[test]
+    fn test_transformed_move_set_and_agents() {
+        let offsets = vec![(0, 1), (1, 2)];
+        let map = env("1..\n...\n..2\n")
+            .with_move_set(MoveSet::custom(offsets))
+            .with_agents(vec![
+                Agent { player: 1, start: (0, 0), goal: (0, 2) },
+                Agent { player: 2, start: (2, 2), goal: (2, 0) },
+            ])
+            .unwrap()
+            .with_transform(Transform::Rotate90);
+
+        assert_eq!(map.definition.move_set.offsets, vec![(1, 0), (2, -1)]);
+        assert_eq!(map.definition.agents[0].start, (0, 2));
+        assert_eq!(map.definition.agents[0].goal, (2, 2));
+
+        // Symmetric move sets keep their offsets in order
+        let knight = env("1.A\nB.2\n").with_move_set(MoveSet::custom(KNIGHT_MOVES.to_vec()));
+        assert_eq!(knight.with_transform(Transform::Transpose).definition.move_set.offsets, KNIGHT_MOVES.to_vec());
+    }
+
+    #[test]
+    fn test_variants() {
+        let map = MAPFEnvironment::new_from_file("./maps/hall.txt").unwrap();
+        let variants = map.variants();
+        let cells = |matrix: &SparseMatrix2D| {
+            let mut cells = matrix.get_nnz();
+            cells.sort();
+            cells
+        };
+
+        assert_eq!(variants.len(), 16);
+        assert!(variants.iter().all(|variant| variant.validate().is_empty() == map.validate().is_empty()));
+
+        // Variants come in pairs per transform, Rotate90 is the second transform
+        let rotated = &variants[2].definition;
+        assert_eq!(rotated.shape, (10, 7));
+        assert_eq!(cells(&rotated.obstacles), vec![(2, 3, 1), (7, 3, 1)]);
+        assert_eq!(cells(&rotated.goals), vec![(2, 2, 1), (7, 2, 2)]);
+        assert_eq!(cells(&rotated.starting_positions), vec![(2, 4, 1), (7, 4, 2)]);
+
+        let mirrored = &variants[8].definition;
+        assert_eq!(mirrored.shape, (7, 10));
+        assert_eq!(cells(&mirrored.obstacles), vec![(3, 2, 1), (3, 7, 1)]);
+        assert_eq!(cells(&mirrored.goals), vec![(4, 2, 2), (4, 7, 1)]);
+        assert_eq!(cells(&mirrored.starting_positions), vec![(2, 2, 2), (2, 7, 1)]);
+
+        let swapped = &variants[9].definition;
+        assert_eq!(cells(&swapped.goals), vec![(4, 2, 1), (4, 7, 2)]);
+        assert_eq!(cells(&swapped.starting_positions), vec![(2, 2, 1), (2, 7, 2)]);
+    }
+
+    #[test]
+    fn test_evaluate_variants() {
+        // Player 1 is a step from its goal and player 2 three steps, so whoever holds the left side wins
+        let map = env("1A..2..B
+");
+        let actors = || -> Vec<Box<dyn AI>> { vec![Box::new(GreedyAI::new()), Box::new(GreedyAI::new())] };
+        let outcomes = evaluate_variants(&map, actors, EvaluateAIParams::default());
+
+        assert_eq!(outcomes.len(), 16);
+        for (idx, outcome) in outcomes.into_iter().enumerate() {
+            let winner = if idx % 2 == 0 { 1 } else { 2 };
+            assert_eq!(outcome.unwrap().status, StateStatus::Winner(winner), "variant {}", idx);
+        }
+    }
+}
@@ -0,0 +1,224 @@
+use crate::mapf::environment::MAPFEnvironment;
+use thiserror::Error;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, PartialOrd, Ord)]
+pub enum Severity {
+    /// The map plays, but probably not as intended
+    Warning,
+    /// A player can never reach all of its goals
+    Error,
+}
+
+/// A problem found by `MAPFEnvironment::validate`, cells are given as (row, column)
+#[derive(Clone, Debug, Error, Eq, PartialEq)]
+pub enum Problem {
+    #[error("Player {0} has no goals and can only win by elimination")]
+    NoGoals(u8),
+
+    #[error("Player {player} has {goals} goals but only {units} units")]
+    MoreGoalsThanUnits { player: u8, goals: u64, units: u64 },
+
+    #[error("Goal of player {player} at {cell:?} can not be reached by any of its units")]
+    UnreachableGoal { player: u8, cell: (usize, usize) },
+
+    #[error("Unit of player {player} at {cell:?} can not reach any of its goals")]
+    StrandedUnit { player: u8, cell: (usize, usize) },
+
+    #[error("Unit of player {player} at {cell:?} starts on a goal of player {owner}")]
+    StartOnGoal { player: u8, owner: u8, cell: (usize, usize) },
+
+    #[error("{regions} separate regions of free cells, the region around {cell:?} is cut off from the first one")]
+    Disconnected { regions: usize, cell: (usize, usize) },
+
+    #[error("Units of player {player} are {total} steps from their goals in total, those of player {other} {other_total}")]
+    AsymmetricDistances { player: u8, total: u32, other: u8, other_total: u32 },
+}
+
+impl Problem {
+    pub fn severity(&self) -> Severity {
+        match self {
+            Problem::MoreGoalsThanUnits { .. } | Problem::UnreachableGoal { .. } => Severity::Error,
+            _ => Severity::Warning,
+        }
+    }
+}
+
+impl MAPFEnvironment {
+    /// Lints the map for problems that otherwise only show up as odd game results, errors first
+    pub fn validate(&self) -> Vec<Problem> {
+        let definition = &self.definition;
+        let mut problems = Vec::new();
+
+        let units = |player: u8| definition.starting_positions.count_value(player);
+        let mut players: Vec<u8> = definition.players.clone();
+        players.extend((1..definition.goals_num.len() as u8).filter(|&p| definition.goals_of(p) > 0 && units(p) == 0));
+        players.sort();
+
+        for &player in &players {
+            let goals = definition.goals_of(player);
+
+            if goals == 0 {
+                problems.push(Problem::NoGoals(player));
+            } else if goals > units(player) && !definition.is_labelled() {
+                problems.push(Problem::MoreGoalsThanUnits { player, goals, units: units(player) });
+            }
+        }
+
+        // Goals are reached from every unit of the player, or from its agent in labelled mode
+        let starts = definition.starting_positions.get_nnz();
+        let reach: Vec<Vec<Vec<Option<u32>>>> = starts.iter()
+            .map(|&(a0, a1, _)| definition.step_distances((a0, a1)))
+            .collect();
+
+        for (g0, g1, owner) in definition.goals.get_nnz() {
+            let reached = starts.iter().zip(&reach).any(|(&(a0, a1, player), distances)| {
+                let own = match definition.agents.iter().find(|agent| agent.start == (a0, a1)) {
+                    Some(agent) => agent.goal == (g0, g1),
+                    None => player == owner,
+                };
+                own && distances[g0][g1].is_some()
+            });
+
+            if !reached && units(owner) > 0 {
+                problems.push(Problem::UnreachableGoal { player: owner, cell: (g0, g1) });
+            }
+        }
+
+        for &(a0, a1, player) in &starts {
+            let owner = definition.goals.get(a0, a1).unwrap_or(0);
+            if owner != 0 {
+                problems.push(Problem::StartOnGoal { player, owner, cell: (a0, a1) });
+            }
+        }
+
+        // Units are listed in the same order by `goal_distances`
+        let distances = definition.goal_distances();
+        for &player in &definition.players {
+            if definition.goals_of(player) == 0 {
+                continue;
+            }
+
+            let units = starts.iter().filter(|&&(_, _, p)| p == player);
+            for (&(a0, a1, _), distance) in units.zip(&distances[player as usize]) {
+                if distance.is_none() {
+                    problems.push(Problem::StrandedUnit { player, cell: (a0, a1) });
+                }
+            }
+        }
+
+        problems.extend(self.disconnected_regions());
+
+        // Distances are compared in sorted order, so that only the shape of the map matters
+        let summaries: Vec<(u8, Vec<u32>)> = definition.players.iter()
+            .filter(|&&player| definition.goals_of(player) > 0)
+            .map(|&player| {
+                let mut reachable: Vec<u32> = distances[player as usize].iter().flatten().copied().collect();
+                reachable.sort();
+                (player, reachable)
+            })
+            .collect();
+
+        for pair in summaries.windows(2) {
+            let ((player, first), (other, second)) = (&pair[0], &pair[1]);
+
+            if first != second {
+                problems.push(Problem::AsymmetricDistances {
+                    player: *player,
+                    total: first.iter().sum(),
+                    other: *other,
+                    other_total: second.iter().sum(),
+                });
+            }
+        }
+
+        problems.sort_by_key(|problem| std::cmp::Reverse(problem.severity()));
+        problems
+    }
+
+    /// One problem for every region of free cells beyond the first, in reading order
+    fn disconnected_regions(&self) -> Vec<Problem> {
+        let definition = &self.definition;
+        let (height, width) = definition.shape;
+        let mut region = vec![vec![0usize; width]; height];
+        let mut firsts: Vec<(usize, usize)> = Vec::new();
+
+        for a0 in 0..height {
+            for a1 in 0..width {
+                if region[a0][a1] != 0 || definition.obstacles.get(a0, a1).unwrap_or(0) != 0 {
+                    continue;
+                }
+
+                firsts.push((a0, a1));
+                for (r0, row) in definition.step_distances((a0, a1)).iter().enumerate() {
+                    for (r1, distance) in row.iter().enumerate() {
+                        if distance.is_some() && region[r0][r1] == 0 {
+                            region[r0][r1] = firsts.len();
+                        }
+                    }
+                }
+            }
+        }
+
+        firsts.iter()
+            .skip(1)
+            .map(|&cell| Problem::Disconnected { regions: firsts.len(), cell })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mapf::definition::Agent;
+    use crate::mapf::environment::MAPFEnvironment;
+    use crate::mapf::validation::{Problem, Severity};
+
+    fn problems(grid: &str) -> Vec<Problem> {
+        MAPFEnvironment::from_text(grid).unwrap().validate()
+    }
+
+    #[test]
+    fn test_valid_maps() {
+        assert!(problems("1..A\nB..2\n").is_empty());
+        assert_eq!(MAPFEnvironment::new_from_file("./maps/defender.txt").unwrap().validate(), vec![Problem::NoGoals(2)]);
+        assert_eq!(Problem::NoGoals(2).severity(), Severity::Warning);
+    }
+
+    #[test]
+    fn test_goal_problems() {
+        assert_eq!(problems("1..A\n...2\n"), vec![Problem::NoGoals(2)]);
+        assert_eq!(problems("1.AA\nB.2.\n"), vec![Problem::MoreGoalsThanUnits { player: 1, goals: 2, units: 1 }]);
+        assert_eq!(problems("1.AC\nB.2.\n"), vec![Problem::MoreGoalsThanUnits { player: 3, goals: 1, units: 0 }]);
+    }
+
+    #[test]
+    fn test_connectivity() {
+        let found = problems("1.#A\nB.#2\n");
+
+        assert_eq!(found[0].severity(), Severity::Error);
+        assert!(found.contains(&Problem::UnreachableGoal { player: 1, cell: (0, 3) }));
+        assert!(found.contains(&Problem::UnreachableGoal { player: 2, cell: (1, 0) }));
+        assert!(found.contains(&Problem::StrandedUnit { player: 1, cell: (0, 0) }));
+        assert!(found.contains(&Problem::Disconnected { regions: 2, cell: (0, 3) }));
+    }
+
+    #[test]
+    fn test_asymmetric_distances() {
+        let found = problems("1...A\nB.2..\n");
+        assert_eq!(found, vec![Problem::AsymmetricDistances { player: 1, total: 4, other: 2, other_total: 2 }]);
+        assert_eq!(found[0].to_string(), "Units of player 1 are 4 steps from their goals in total, those of player 2 2");
+    }
+
+    #[test]
+    fn test_labelled_start_on_goal() {
+        let env = MAPFEnvironment::from_text("1..2\n....\n").unwrap()
+            .with_agents(vec![
+                Agent { player: 1, start: (0, 0), goal: (0, 3) },
+                Agent { player: 2, start: (0, 3), goal: (0, 0) },
+            ])
+            .unwrap();
+
+        let found = env.validate();
+        assert!(found.contains(&Problem::StartOnGoal { player: 1, owner: 2, cell: (0, 0) }));
+        assert!(found.iter().all(|problem| problem.severity() == Severity::Warning));
+    }
+}
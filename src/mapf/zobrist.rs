@@ -0,0 +1,209 @@
+use crate::mapf::action::UnitMove;
+use crate::mapf::state::MAPFState;
+use crate::mapf::state::SpecialState::No;
+
+/// Which of the three unit matrices of a `MAPFState` a unit is in
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Stage {
+    Begin = 0,
+    Available = 1,
+    Moved = 2,
+}
+
+const LABEL_TAG: u64 = 3;
+const SIDE_TO_MOVE_TAG: u64 = 4;
+const POINTS_TAG: u64 = 5;
+const MOVE_TAG: u64 = 6;
+
+/// Keys are derived from their index instead of being kept in a table, so any map size works
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+fn key(cell: (usize, usize), tag: u64, value: u64) -> u64 {
+    splitmix64(((cell.0 as u64) << 42) ^ ((cell.1 as u64) << 20) ^ (tag << 16) ^ value)
+}
+
+/// Key of a unit of `player` standing on `cell` in `stage`
+pub fn unit_key(cell: (usize, usize), stage: Stage, player: u8) -> u64 {
+    key(cell, stage as u64, player as u64)
+}
+
+/// Key of agent `agent` standing on `cell` at the start of the turn, only used in labelled mode
+pub fn label_key(cell: (usize, usize), agent: u16) -> u64 {
+    key(cell, LABEL_TAG, agent as u64)
+}
+
+/// Key of a unit on `cell` having `points` movement points saved up
+pub fn points_key(cell: (usize, usize), points: u8) -> u64 {
+    key(cell, POINTS_TAG, points as u64)
+}
+
+/// Key of a move made this round. Both ends go into a single key, so that it matters which unit went where,
+/// and moves stay hashed when they are hidden from the next player in simultaneous mode.
+pub fn move_key(m: &UnitMove) -> u64 {
+    splitmix64(key(m.from, MOVE_TAG, m.player as u64) ^ key(m.to, MOVE_TAG, m.agent as u64).rotate_left(17))
+}
+
+pub fn side_to_move_key(player: u8) -> u64 {
+    key((0, 0), SIDE_TO_MOVE_TAG, player as u64)
+}
+
+impl MAPFState {
+    /// Zobrist key computed from scratch, `MAPFState::zobrist` is kept equal to it by every transition
+    pub fn compute_zobrist(&self) -> u64 {
+        if self.special_state != No {
+            return 0;
+        }
+
+        let mut zobrist = side_to_move_key(self.playing);
+
+        for (matrix, stage) in [
+            (&self.units_begin, Stage::Begin),
+            (&self.units_available, Stage::Available),
+            (&self.units_moved, Stage::Moved),
+        ] {
+            for (a0, a1, player) in matrix.get_nnz() {
+                zobrist ^= unit_key((a0, a1), stage, player);
+            }
+        }
+
+        for (a0, a1, agent) in self.labels.get_nnz() {
+            zobrist ^= label_key((a0, a1), agent);
+        }
+
+        for (a0, a1, points) in self.points.get_nnz() {
+            zobrist ^= points_key((a0, a1), points);
+        }
+
+        for m in &self.moves {
+            zobrist ^= move_key(m);
+        }
+
+        zobrist
+    }
+
+    /// Part of the key contributed by the units, label and points on `cell`
+    pub(crate) fn cell_zobrist(&self, (a0, a1): (usize, usize)) -> u64 {
+        let mut zobrist = 0;
+
+        for (matrix, stage) in [
+            (&self.units_begin, Stage::Begin),
+            (&self.units_available, Stage::Available),
+            (&self.units_moved, Stage::Moved),
+        ] {
+            let player = matrix.get(a0, a1).unwrap_or(0);
+            if player != 0 {
+                zobrist ^= unit_key((a0, a1), stage, player);
+            }
+        }
+
+        let agent = self.labels.get(a0, a1).unwrap_or(0);
+        if agent != 0 {
+            zobrist ^= label_key((a0, a1), agent);
+        }
+
+        let points = self.points.get(a0, a1).unwrap_or(0);
+        if points != 0 {
+            zobrist ^= points_key((a0, a1), points);
+        }
+
+        zobrist
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::deps::state_definition::{StateEnvironment, StateStatus};
+    use crate::mapf::action::MAPFAction;
+    use crate::mapf::environment::{MAPFEnvironment, TurnMode};
+
+    #[test]
+    fn test_zobrist_consistency() {
+        let problems = [
+            MAPFEnvironment::new_from_file("./maps/defender.txt").unwrap(),
+            MAPFEnvironment::new_from_file("./maps/test_two_goals.test.txt").unwrap().labelled().unwrap(),
+            MAPFEnvironment::new_from_file("./maps/test_three_way.test.txt").unwrap().with_turn_mode(TurnMode::Simultaneous),
+        ];
+
+        for problem in problems {
+            let mut state = problem.get_initial_state();
+            assert_eq!(state.zobrist, state.compute_zobrist());
+            assert_eq!(state.history.iter().collect::<Vec<_>>(), vec![state.zobrist]);
+
+            for step in 0..20 {
+                let actions = problem.get_actions(&state);
+                for action in actions.iter() {
+                    let next = problem.next(&state, action);
+                    assert_eq!(next.zobrist, next.compute_zobrist(), "{:?}", action);
+                }
+
+                state = problem.next(&state, &actions[(step * 7) % actions.len()]);
+                if problem.get_status(&state) != StateStatus::Running {
+                    break;
+                }
+            }
+
+            let forfeited = problem.forfeit(&state);
+            assert_eq!(forfeited.zobrist, forfeited.compute_zobrist());
+        }
+    }
+
+    #[test]
+    fn test_zobrist_transpositions() {
+        let problem = MAPFEnvironment::new_from_file("./maps/defender.txt").unwrap();
+        let state = problem.get_initial_state();
+
+        let a = MAPFAction::Move((1, 1), (2, 1));
+        let b = MAPFAction::Move((1, 2), (2, 2));
+        let ab = problem.next(&problem.next(&state, &a), &b);
+        let ba = problem.next(&problem.next(&state, &b), &a);
+
+        assert_eq!(ab.zobrist, ba.zobrist);
+        assert_ne!(ab.zobrist, state.zobrist);
+    }
+
+    #[test]
+    fn test_zobrist_points_and_hidden_moves() {
+        let problem = MAPFEnvironment::new_from_file("./maps/test_swamp.test.txt").unwrap().with_turn_mode(TurnMode::Simultaneous);
+        let state = problem.get_initial_state();
+
+        // States differing only in saved movement points are different positions
+        let mut saved = state.clone();
+        let (a0, a1, points) = saved.points.get_nnz()[0];
+        saved.points.insert(a0, a1, points + 1);
+        saved.zobrist = saved.compute_zobrist();
+        assert_ne!(saved.zobrist, state.zobrist);
+
+        // Moves committed by the first player are hidden from the second one, but still tell states apart
+        let moves: Vec<MAPFAction> = problem.get_actions(&state).iter()
+            .filter(|action| matches!(action, MAPFAction::Move(from, to) if from != to))
+            .cloned()
+            .collect();
+        let committed: Vec<_> = moves.iter()
+            .map(|action| problem.next(&problem.next(&state, action), &MAPFAction::Commit))
+            .collect();
+
+        assert!(committed.len() > 1);
+        assert_ne!(committed[0], committed[1]);
+        assert_eq!(committed[0].units_available, committed[1].units_available);
+        assert_ne!(committed[0].zobrist, committed[1].zobrist);
+    }
+
+    #[test]
+    fn test_history_is_shared() {
+        let problem = MAPFEnvironment::new_from_file("./maps/defender.txt").unwrap();
+        let first = problem.get_initial_state();
+        let second = problem.next(&first, &MAPFAction::Commit);
+        let mut third = problem.next(&second, &MAPFAction::Commit);
+
+        assert_eq!(third.history.iter().collect::<Vec<_>>(), vec![third.zobrist, second.zobrist, first.zobrist]);
+        assert_eq!(first.history.iter().collect::<Vec<_>>(), vec![first.zobrist]);
+
+        third.history.pop();
+        assert_eq!(third.history, second.history);
+    }
+}
@@ -0,0 +1,414 @@
+use crate::ai::AI;
+use crate::deps::state_definition::{StateEnvironment, StateStatus};
+use crate::loops::{check_actors, play_action, ActorCountMismatch};
+use crate::mapf::action::MAPFAction;
+use crate::mapf::collision::Clash;
+use crate::mapf::environment::MAPFEnvironment;
+use crate::mapf::record::{GameRecord, RecordEntry, RecordError};
+use crate::mapf::state::MAPFState;
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use crossterm::style::{Attribute, Color, Print, ResetColor, SetAttribute, SetForegroundColor};
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{cursor, execute, queue};
+use std::io::{self, Write};
+
+/// Colours of players 1 to 9
+const PLAYER_COLOURS: [Color; 9] = [
+    Color::Red,
+    Color::Blue,
+    Color::Green,
+    Color::Yellow,
+    Color::Magenta,
+    Color::Cyan,
+    Color::DarkRed,
+    Color::DarkBlue,
+    Color::DarkGreen,
+];
+
+/// Actions played before a live game is given up on
+const LIVE_ACTION_LIMIT: usize = 100_000;
+
+/// Actions listed in the side panel
+const LAST_ACTIONS: usize = 8;
+
+fn player_colour(player: u8) -> Color {
+    PLAYER_COLOURS.get(player.wrapping_sub(1) as usize).copied().unwrap_or(Color::White)
+}
+
+/// Piece of text drawn in a single style
+#[derive(Clone, Debug, PartialEq)]
+pub struct Span {
+    pub text: String,
+    pub colour: Option<Color>,
+    /// Drawn in reverse video, marks units standing on their own goals and clashes
+    pub highlight: bool,
+}
+
+impl Span {
+    fn plain(text: impl Into<String>) -> Self {
+        Span { text: text.into(), colour: None, highlight: false }
+    }
+
+    fn coloured(text: impl Into<String>, colour: Color) -> Self {
+        Span { text: text.into(), colour: Some(colour), highlight: false }
+    }
+}
+
+/// A game being viewed, either a loaded record or a live game whose actions are played as the viewer steps
+/// past the last one
+pub struct Replay {
+    env: MAPFEnvironment,
+    record: GameRecord,
+    /// The initial state and the state after every action of the record
+    states: Vec<MAPFState>,
+    /// Players of a live game, empty for records
+    actors: Vec<Box<dyn AI>>,
+    position: usize,
+}
+
+impl Replay {
+    pub fn from_record(record: GameRecord) -> Result<Self, RecordError> {
+        Ok(Replay { env: record.environment()?, states: record.states()?, record, actors: Vec::new(), position: 0 })
+    }
+
+    pub fn live(env: MAPFEnvironment, actors: Vec<Box<dyn AI>>) -> Result<Self, ActorCountMismatch> {
+        check_actors(&env, &actors)?;
+
+        let mut record = GameRecord::new(&env);
+        record.players = env.definition.players.iter()
+            .zip(actors.iter())
+            .map(|(&player, actor)| (player, actor.name()))
+            .collect();
+
+        Ok(Replay { states: vec![env.get_initial_state()], env, record, actors, position: 0 })
+    }
+
+    pub fn state(&self) -> &MAPFState {
+        &self.states[self.position]
+    }
+
+    fn is_over(&self, state: &MAPFState) -> bool {
+        self.env.get_status(state) != StateStatus::Running
+    }
+
+    /// Makes sure the state after the current one is known, playing it in a live game
+    fn extend(&mut self) -> bool {
+        if self.position + 1 < self.states.len() {
+            return true;
+        }
+
+        let mut state = self.states.last().unwrap().clone();
+        if self.actors.is_empty() || self.is_over(&state) || self.states.len() > LIVE_ACTION_LIMIT {
+            return false;
+        }
+
+        play_action(&self.env, &mut self.actors, &mut state, &mut self.record, false);
+        self.states.push(state);
+        true
+    }
+
+    pub fn step_forward(&mut self) -> bool {
+        if !self.extend() {
+            return false;
+        }
+
+        self.position += 1;
+        true
+    }
+
+    pub fn step_back(&mut self) -> bool {
+        if self.position == 0 {
+            return false;
+        }
+
+        self.position -= 1;
+        true
+    }
+
+    /// Whether the action leading to the current state ended a turn, a commit or a forfeit
+    fn at_turn_boundary(&self) -> bool {
+        self.position == 0 || matches!(self.record.entries[self.position - 1].action, None | Some(MAPFAction::Commit))
+    }
+
+    pub fn turn_forward(&mut self) {
+        while self.step_forward() && !self.at_turn_boundary() {}
+    }
+
+    pub fn turn_back(&mut self) {
+        if self.step_back() {
+            while !self.at_turn_boundary() {
+                self.step_back();
+            }
+        }
+    }
+
+    pub fn rewind(&mut self) {
+        self.position = 0;
+    }
+
+    pub fn fast_forward(&mut self) {
+        while self.step_forward() {}
+    }
+
+    /// The current state drawn as lines of text, the grid on the left and the side panel on the right
+    pub fn frame(&self) -> Vec<Vec<Span>> {
+        let grid = self.grid();
+        let panel = self.panel();
+        let width = 2 * self.env.definition.shape.1 + 3;
+
+        (0..grid.len().max(panel.len()))
+            .map(|idx| {
+                let mut line = grid.get(idx).cloned().unwrap_or_else(|| vec![Span::plain(" ".repeat(width - 3))]);
+                line.push(Span::plain("   "));
+                line.extend(panel.get(idx).cloned().unwrap_or_default());
+                line
+            })
+            .collect()
+    }
+
+    fn grid(&self) -> Vec<Vec<Span>> {
+        let state = self.state();
+        let definition = &self.env.definition;
+
+        let mut clashes: Vec<(usize, usize)> = Vec::new();
+        for clash in &state.clashes {
+            match clash {
+                Clash::Vertex { cell, .. } => clashes.push(*cell),
+                Clash::Edge { cells: (from, to), .. } => clashes.extend([*from, *to]),
+            }
+        }
+
+        (0..definition.shape.0)
+            .map(|a0| {
+                (0..definition.shape.1)
+                    .map(|a1| {
+                        let begin = state.units_begin.get(a0, a1).unwrap_or(0);
+                        let available = state.units_available.get(a0, a1).unwrap_or(0);
+                        let moved = state.units_moved.get(a0, a1).unwrap_or(0);
+                        let goal = definition.goals.get(a0, a1).unwrap_or(0);
+
+                        let mut span = if definition.obstacles.get(a0, a1).unwrap_or(0) != 0 {
+                            Span::coloured("██", Color::DarkGrey)
+                        } else if moved != 0 {
+                            Span::coloured(format!("{}›", moved), player_colour(moved))
+                        } else if available != 0 {
+                            Span::coloured(format!("{} ", available), player_colour(available))
+                        } else if begin != 0 {
+                            // The unit has moved away this turn
+                            Span::coloured("· ", player_colour(begin))
+                        } else if goal != 0 {
+                            Span::coloured(format!("{} ", (b'A' + goal - 1) as char), player_colour(goal))
+                        } else if definition.cost((a0, a1)) > 1 {
+                            let symbol = definition.terrain_symbol(definition.cost((a0, a1))).unwrap_or('?');
+                            Span::coloured(format!("{} ", symbol), Color::DarkYellow)
+                        } else {
+                            Span::coloured(". ", Color::DarkGrey)
+                        };
+
+                        let unit = if moved != 0 { moved } else { available };
+                        span.highlight = unit != 0 && unit == goal;
+
+                        if clashes.contains(&(a0, a1)) {
+                            span.text = format!("✕{}", span.text.chars().next().filter(char::is_ascii_digit).unwrap_or(' '));
+                            span.colour = Some(Color::White);
+                            span.highlight = true;
+                        }
+
+                        span
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn panel(&self) -> Vec<Vec<Span>> {
+        let state = self.state();
+        let outcome = self.env.get_outcome(state);
+        let mut panel: Vec<Vec<Span>> = Vec::new();
+
+        let name = self.env.definition.metadata.name.clone().unwrap_or_else(|| "unnamed map".to_string());
+        let live = if self.actors.is_empty() || self.is_over(self.states.last().unwrap()) { "" } else { "+" };
+        panel.push(vec![Span::plain(format!("{}, action {}/{}{}", name, self.position, self.states.len() - 1, live))]);
+
+        panel.push(vec![
+            Span::plain(format!("Round {}, ", state.round)),
+            Span::coloured(format!("player {}", state.playing), player_colour(state.playing)),
+            Span::plain(" to move"),
+        ]);
+
+        let status = match (outcome.status.clone(), outcome.reason) {
+            (StateStatus::Running, _) => "Running".to_string(),
+            (StateStatus::Winner(player), Some(reason)) => format!("Player {} wins ({:?})", player, reason),
+            (StateStatus::Winner(player), None) => format!("Player {} wins", player),
+            (StateStatus::Draw, Some(reason)) => format!("Draw ({:?})", reason),
+            (StateStatus::Draw, None) => "Draw".to_string(),
+        };
+        panel.push(vec![Span::plain(status)]);
+
+        let mut units = vec![Span::plain("Units / goals held:")];
+        for &player in &self.env.definition.players {
+            units.push(Span::coloured(
+                format!(" {}: {}/{}", player, outcome.units_of(player), outcome.goals_held_by(player)),
+                player_colour(player),
+            ));
+        }
+        panel.push(units);
+
+        panel.push(Vec::new());
+        panel.push(vec![Span::plain("Last actions:")]);
+        let first = self.position.saturating_sub(LAST_ACTIONS);
+        for entry in &self.record.entries[first..self.position] {
+            panel.push(vec![Span::coloured(format!("  {}", Self::describe(entry)), player_colour(entry.player))]);
+        }
+
+        if !state.clashes.is_empty() {
+            panel.push(Vec::new());
+            panel.push(vec![Span::plain("Clashes:")]);
+            for clash in &state.clashes {
+                let text = match clash {
+                    Clash::Vertex { cell, winner, .. } => format!("  in {:?}, won by {}", cell, winner),
+                    Clash::Edge { cells, winner, .. } => format!("  between {:?} and {:?}, won by {}", cells.0, cells.1, winner),
+                };
+                panel.push(vec![Span::plain(text)]);
+            }
+        }
+
+        panel.push(Vec::new());
+        panel.push(vec![Span::plain("←/→ action  ↑/↓ turn  Home/End  q quit")]);
+        panel
+    }
+
+    fn describe(entry: &RecordEntry) -> String {
+        let action = match &entry.action {
+            Some(MAPFAction::Move(from, to)) => format!("{:?} → {:?}", from, to),
+            Some(MAPFAction::Commit) => "commit".to_string(),
+            None => "forfeit".to_string(),
+        };
+
+        format!("{} {} ({:?})", entry.player, action, entry.elapsed)
+    }
+}
+
+/// Shows the replay in the terminal until the user quits
+pub fn run(mut replay: Replay) -> io::Result<()> {
+    let mut stdout = io::stdout();
+
+    terminal::enable_raw_mode()?;
+    execute!(stdout, EnterAlternateScreen, cursor::Hide)?;
+
+    let result = (|| -> io::Result<()> {
+        loop {
+            draw(&mut stdout, &replay.frame())?;
+
+            if let Event::Key(key) = event::read()? {
+                if key.kind != KeyEventKind::Press {
+                    continue;
+                }
+
+                match key.code {
+                    KeyCode::Right | KeyCode::Char('l') => { replay.step_forward(); }
+                    KeyCode::Left | KeyCode::Char('h') => { replay.step_back(); }
+                    KeyCode::Down | KeyCode::Char('j') => replay.turn_forward(),
+                    KeyCode::Up | KeyCode::Char('k') => replay.turn_back(),
+                    KeyCode::Home | KeyCode::Char('g') => replay.rewind(),
+                    KeyCode::End | KeyCode::Char('G') => replay.fast_forward(),
+                    KeyCode::Esc | KeyCode::Char('q') => return Ok(()),
+                    _ => {}
+                }
+            }
+        }
+    })();
+
+    // The terminal is restored even when drawing failed
+    execute!(stdout, cursor::Show, LeaveAlternateScreen)?;
+    terminal::disable_raw_mode()?;
+    result
+}
+
+fn draw(out: &mut impl Write, frame: &[Vec<Span>]) -> io::Result<()> {
+    queue!(out, Clear(ClearType::All))?;
+
+    for (row, line) in frame.iter().enumerate() {
+        queue!(out, cursor::MoveTo(0, row as u16))?;
+
+        for span in line {
+            if let Some(colour) = span.colour {
+                queue!(out, SetForegroundColor(colour))?;
+            }
+            if span.highlight {
+                queue!(out, SetAttribute(Attribute::Reverse))?;
+            }
+            queue!(out, Print(&span.text), SetAttribute(Attribute::Reset), ResetColor)?;
+        }
+    }
+
+    out.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ai::greedy::GreedyAI;
+    use crate::ai::random_ai::RandomAI;
+    use crate::mapf::action::MAPFAction::{Commit, Move};
+    use crate::mapf::environment::MAPFEnvironment;
+    use crate::mapf::record::{GameRecord, RecordEntry};
+    use crate::viewer::Replay;
+    use std::time::Duration;
+
+    fn text(replay: &Replay) -> String {
+        replay.frame()
+            .iter()
+            .map(|line| line.iter().map(|span| span.text.as_str()).collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn test_record_navigation() {
+        let env = MAPFEnvironment::new_from_file("./maps/test_duel.test.txt").unwrap();
+        let mut record = GameRecord::new(&env);
+        record.entries = [(1, Move((0, 1), (1, 1))), (1, Commit), (2, Move((0, 3), (0, 2))), (2, Commit)]
+            .into_iter()
+            .map(|(player, action)| RecordEntry { player, action: Some(action), elapsed: Duration::from_micros(5) })
+            .collect();
+
+        let mut replay = Replay::from_record(record).unwrap();
+        assert!(text(&replay).contains("action 0/4"));
+        assert!(!replay.step_back());
+
+        assert!(replay.step_forward());
+        assert!(text(&replay).contains("A 1›. "));
+        assert!(text(&replay).contains("1 (0, 1) → (1, 1)"));
+
+        replay.turn_forward();
+        assert_eq!(replay.position, 2);
+        replay.turn_forward();
+        assert_eq!(replay.position, 4);
+        assert!(text(&replay).contains("Round 1, player 1 to move"));
+        assert!(!replay.step_forward());
+
+        replay.turn_back();
+        assert_eq!(replay.position, 2);
+        replay.rewind();
+        assert_eq!(replay.position, 0);
+        replay.fast_forward();
+        assert_eq!(replay.position, 4);
+    }
+
+    #[test]
+    fn test_live_game() {
+        let env = MAPFEnvironment::new_from_file("./maps/test_hall_run.test.txt").unwrap();
+        let mut replay = Replay::live(env, vec![Box::new(GreedyAI::new()), Box::new(RandomAI::new())]).unwrap();
+
+        replay.turn_forward();
+        assert!(replay.position > 0);
+        assert!(text(&replay).contains("+"));
+
+        replay.fast_forward();
+        assert!(!text(&replay).contains("Running"));
+        assert_eq!(replay.record.states().unwrap().last(), Some(replay.state()));
+
+        let three_way = MAPFEnvironment::new_from_file("./maps/test_three_way.test.txt").unwrap();
+        assert!(Replay::live(three_way, vec![Box::new(GreedyAI::new()), Box::new(RandomAI::new())]).is_err());
+    }
+}